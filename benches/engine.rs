@@ -0,0 +1,113 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use shields_up_engineering::{
+    agents::{Agent, GreedyAgent, RandomAgent},
+    game::{GameState, Player, System, UserAction, UserActionWithPlayer},
+    simulation::{play_game, DEFAULT_MAX_TURNS},
+};
+
+// a state some way into a game, with cards hot-wired onto the ships
+fn mid_game_state() -> GameState {
+    let mut game_state = GameState::start_state_with_seed(1);
+    let mut agents = [GreedyAgent::new(1), GreedyAgent::new(2)];
+    for _ in 0..60 {
+        let player = game_state.next_player_to_act();
+        let legal_moves = game_state.legal_moves(player);
+        let agent = match player {
+            Player::Player1 => &mut agents[0],
+            Player::Player2 => &mut agents[1],
+        };
+        let user_action_with_player = agent.choose_action(&game_state, player, &legal_moves);
+        game_state
+            .receive_user_action(user_action_with_player)
+            .unwrap();
+    }
+    assert!(game_state.winner().is_none());
+    game_state
+}
+
+fn bench_receive_user_action(c: &mut Criterion) {
+    let game_state = mid_game_state();
+    let player = game_state.next_player_to_act();
+    let legal_move = game_state.legal_moves(player)[0].clone();
+    c.bench_function("receive_user_action legal", |b| {
+        b.iter_batched(
+            || game_state.clone(),
+            |mut game_state| game_state.receive_user_action(black_box(legal_move.clone())),
+            BatchSize::SmallInput,
+        )
+    });
+    let rejected_move = UserActionWithPlayer {
+        player: player.other_player(),
+        user_action: UserAction::StopResolvingEffects,
+    };
+    c.bench_function("receive_user_action rejected", |b| {
+        b.iter_batched(
+            || game_state.clone(),
+            |mut game_state| game_state.receive_user_action(black_box(rejected_move.clone())),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_clone(c: &mut Criterion) {
+    let game_state = mid_game_state();
+    c.bench_function("GameState::clone", |b| {
+        b.iter(|| black_box(&game_state).clone())
+    });
+}
+
+fn bench_compact(c: &mut Criterion) {
+    let game_state = mid_game_state();
+    c.bench_function("GameState::to_compact", |b| {
+        b.iter(|| black_box(&game_state).to_compact())
+    });
+    let compact = game_state.to_compact();
+    c.bench_function("GameState::from_compact", |b| {
+        b.iter(|| GameState::from_compact(black_box(&compact)))
+    });
+    c.bench_function("GameState::compact_hash", |b| {
+        b.iter(|| black_box(&game_state).compact_hash())
+    });
+}
+
+fn bench_get_hot_wire_effects(c: &mut Criterion) {
+    let game_state = mid_game_state();
+    let system_state = System::ALL
+        .into_iter()
+        .map(|system| game_state.player1.get_system_state_immut(system))
+        .max_by_key(|system_state| system_state.hot_wires.len())
+        .unwrap();
+    c.bench_function("SystemState::get_hot_wire_effects", |b| {
+        b.iter(|| black_box(system_state).get_hot_wire_effects())
+    });
+}
+
+fn bench_random_game(c: &mut Criterion) {
+    // whole games take long enough that a few samples are plenty
+    let mut group = c.benchmark_group("games");
+    group.sample_size(10);
+    // cycling through a few seeds evens out how long the games run
+    let mut seed = 0;
+    group.bench_function("random game", |b| {
+        b.iter(|| {
+            seed = (seed + 1) % 16;
+            let mut agents: [Box<dyn Agent>; 2] = [
+                Box::new(RandomAgent::new(seed)),
+                Box::new(RandomAgent::new(seed + 1)),
+            ];
+            let game_state = GameState::start_state_with_seed(seed);
+            play_game(game_state, &mut agents, DEFAULT_MAX_TURNS, false)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_receive_user_action,
+    bench_clone,
+    bench_compact,
+    bench_get_hot_wire_effects,
+    bench_random_game
+);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+#![no_main]
+
+// a whole game's worth of actions, mixing legal moves, which get the game into deeper states,
+// with arbitrary ones like a misbehaving client would send
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use shields_up_engineering::game::{GameState, UserActionWithPlayer};
+
+#[derive(Debug, Arbitrary)]
+enum Step {
+    LegalMove(usize),
+    UserAction(UserActionWithPlayer),
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    seed: u64,
+    steps: Vec<Step>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut game_state = GameState::start_state_with_seed(input.seed);
+    for step in input.steps {
+        if game_state.winner().is_some() {
+            return;
+        }
+        let user_action_with_player = match step {
+            Step::LegalMove(index) => {
+                let legal_moves = game_state.legal_moves(game_state.next_player_to_act());
+                if legal_moves.is_empty() {
+                    return;
+                }
+                legal_moves[index % legal_moves.len()].clone()
+            }
+            Step::UserAction(user_action_with_player) => user_action_with_player,
+        };
+        let game_state_before = game_state.clone();
+        if game_state.receive_user_action(user_action_with_player).is_err() {
+            assert_eq!(game_state, game_state_before);
+        }
+    }
+});
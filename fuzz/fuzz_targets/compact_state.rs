@@ -0,0 +1,16 @@
+#![no_main]
+
+// stored compact states that may have been cut short or tampered with
+
+use libfuzzer_sys::fuzz_target;
+use shields_up_engineering::game::GameState;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(game_state) = GameState::from_compact(data) else {
+        return;
+    };
+    // whatever decodes has to survive another round trip and hash the same afterwards
+    let decoded = GameState::from_compact(&game_state.to_compact()).unwrap();
+    assert_eq!(decoded, game_state);
+    assert_eq!(decoded.compact_hash(), game_state.compact_hash());
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+// the bytes a client could send for one action, applied to a seeded start state
+
+use libfuzzer_sys::fuzz_target;
+use shields_up_engineering::game::{GameState, UserActionWithPlayer};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((seed, json)) = data.split_first_chunk::<8>() else {
+        return;
+    };
+    let Ok(json) = std::str::from_utf8(json) else {
+        return;
+    };
+    let Ok(user_action_with_player) = serde_json::from_str::<UserActionWithPlayer>(json) else {
+        return;
+    };
+    let mut game_state = GameState::start_state_with_seed(u64::from_le_bytes(*seed));
+    let game_state_before = game_state.clone();
+    if game_state.receive_user_action(user_action_with_player).is_err() {
+        assert_eq!(game_state, game_state_before);
+    }
+});
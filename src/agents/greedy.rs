@@ -0,0 +1,54 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::agents::Agent;
+use crate::evaluation::evaluate;
+use crate::game::{GameState, Player, UserActionWithPlayer};
+
+// plays whichever legal move leads to the best evaluated state one step ahead
+pub struct GreedyAgent {
+    rng: StdRng,
+}
+
+impl GreedyAgent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for GreedyAgent {
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player: Player,
+        legal_moves: &[UserActionWithPlayer],
+    ) -> UserActionWithPlayer {
+        let mut best_moves = vec![];
+        let mut best_score = f64::NEG_INFINITY;
+        for user_action_with_player in legal_moves {
+            let score = score_move(game_state, player, user_action_with_player);
+            if score > best_score {
+                best_score = score;
+                best_moves.clear();
+            }
+            if score == best_score {
+                best_moves.push(user_action_with_player);
+            }
+        }
+        // break ties randomly so equal moves don't always go to the first card or system
+        best_moves[self.rng.gen_range(0..best_moves.len())].clone()
+    }
+}
+
+fn score_move(
+    game_state: &GameState,
+    player: Player,
+    user_action_with_player: &UserActionWithPlayer,
+) -> f64 {
+    let mut next_state = game_state.clone();
+    match next_state.receive_user_action(user_action_with_player.clone()) {
+        Ok(()) => evaluate(&next_state, player).total(),
+        Err(_) => f64::NEG_INFINITY,
+    }
+}
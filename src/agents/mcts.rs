@@ -0,0 +1,247 @@
+use std::time::{Duration, Instant};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::agents::Agent;
+use crate::evaluation::evaluate;
+use crate::game::{GameRng, GameState, Player, UserActionWithPlayer};
+
+// evaluations are squashed into a win probability with this scale
+const EVALUATION_SCALE: f64 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strength {
+    Easy,
+    Medium,
+    Hard,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsConfig {
+    // stops at whichever budget runs out first
+    pub iterations: usize,
+    pub time_limit: Duration,
+    pub exploration: f64,
+    // random moves played out from a new node before evaluating the state, random moves
+    // are noisy enough that it is usually better to evaluate the new node straight away
+    pub playout_depth: usize,
+}
+
+impl MctsConfig {
+    pub fn with_strength(strength: Strength) -> Self {
+        let (iterations, time_limit) = match strength {
+            Strength::Easy => (50, Duration::from_millis(250)),
+            Strength::Medium => (300, Duration::from_secs(1)),
+            Strength::Hard => (2000, Duration::from_secs(5)),
+        };
+        Self {
+            iterations,
+            time_limit,
+            exploration: 0.25,
+            playout_depth: 0,
+        }
+    }
+}
+
+// information set MCTS: every iteration deals the cards the player cannot see at random
+// (the opponent's hand and the deck order) and searches one shared tree over all the deals
+pub struct MctsAgent {
+    config: MctsConfig,
+    rng: StdRng,
+}
+
+impl MctsAgent {
+    pub fn new(config: MctsConfig, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+struct Node {
+    // the move that led here and who made it, None for the root
+    user_action_with_player: Option<UserActionWithPlayer>,
+    children: Vec<usize>,
+    visits: u32,
+    // how often this node could have been chosen, which differs between deals
+    availability: u32,
+    // summed rewards for the player who made the move
+    total_reward: f64,
+}
+
+impl Node {
+    fn new(user_action_with_player: Option<UserActionWithPlayer>) -> Self {
+        Self {
+            user_action_with_player,
+            children: vec![],
+            visits: 0,
+            availability: 0,
+            total_reward: 0.0,
+        }
+    }
+
+    fn upper_confidence_bound(&self, exploration: f64) -> f64 {
+        let visits = self.visits as f64;
+        self.total_reward / visits
+            + exploration * ((self.availability.max(1) as f64).ln() / visits).sqrt()
+    }
+}
+
+impl Agent for MctsAgent {
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player: Player,
+        legal_moves: &[UserActionWithPlayer],
+    ) -> UserActionWithPlayer {
+        if legal_moves.len() == 1 {
+            return legal_moves[0].clone();
+        }
+        let start = Instant::now();
+        let mut nodes = vec![Node::new(None)];
+        for _ in 0..self.config.iterations {
+            if start.elapsed() > self.config.time_limit {
+                break;
+            }
+            let game_state = determinize(game_state, player, &mut self.rng);
+            self.iterate(&mut nodes, game_state, player);
+        }
+        let best_child = nodes[0]
+            .children
+            .iter()
+            .map(|&child| &nodes[child])
+            .filter(|child| legal_moves.contains(child.user_action_with_player.as_ref().unwrap()))
+            .max_by_key(|child| child.visits);
+        match best_child {
+            Some(child) => child.user_action_with_player.clone().unwrap(),
+            None => legal_moves.choose(&mut self.rng).unwrap().clone(),
+        }
+    }
+}
+
+impl MctsAgent {
+    fn iterate(&mut self, nodes: &mut Vec<Node>, mut game_state: GameState, root_player: Player) {
+        let mut path = vec![0];
+        let mut node = 0;
+        // selection and expansion
+        loop {
+            // the root player may be asked to move even when the opponent could move too
+            let player = if node == 0 {
+                root_player
+            } else {
+                game_state.next_player_to_act()
+            };
+            let mut untried_moves = vec![];
+            let mut available_children = 0;
+            let mut best_child = None;
+            let mut best_bound = f64::NEG_INFINITY;
+            for (user_action_with_player, next_state) in
+                game_state.legal_moves_with_outcomes(player)
+            {
+                let child = nodes[node].children.iter().copied().find(|&child| {
+                    nodes[child].user_action_with_player.as_ref() == Some(&user_action_with_player)
+                });
+                match child {
+                    Some(child) => {
+                        available_children += 1;
+                        nodes[child].availability += 1;
+                        let bound = nodes[child].upper_confidence_bound(self.config.exploration);
+                        if bound > best_bound {
+                            best_bound = bound;
+                            best_child = Some((child, next_state));
+                        }
+                    }
+                    None => untried_moves.push((user_action_with_player, next_state)),
+                }
+            }
+            // progressive widening: only try another move once the ones we have are well visited,
+            // and try the moves that look best one step ahead first
+            let max_children = 1 + (nodes[node].visits as f64).sqrt() as usize;
+            if !untried_moves.is_empty()
+                && (available_children < max_children || best_child.is_none())
+            {
+                let (user_action_with_player, next_state) = untried_moves
+                    .into_iter()
+                    .map(|(user_action_with_player, next_state)| {
+                        let score = evaluate(&next_state, player).total() + self.rng.gen::<f64>();
+                        (score, user_action_with_player, next_state)
+                    })
+                    .max_by(|(a, _, _), (b, _, _)| a.total_cmp(b))
+                    .map(|(_, user_action_with_player, next_state)| {
+                        (user_action_with_player, next_state)
+                    })
+                    .unwrap();
+                game_state = next_state;
+                let child = nodes.len();
+                let mut new_node = Node::new(Some(user_action_with_player));
+                new_node.availability = 1;
+                nodes.push(new_node);
+                nodes[node].children.push(child);
+                path.push(child);
+                break;
+            }
+            let Some((child, next_state)) = best_child else {
+                // the game is over
+                break;
+            };
+            node = child;
+            game_state = next_state;
+            path.push(node);
+        }
+
+        let root_player_reward = self.playout(game_state, root_player);
+        for node in path {
+            let node = &mut nodes[node];
+            node.visits += 1;
+            node.total_reward += match &node.user_action_with_player {
+                Some(user_action_with_player) if user_action_with_player.player != root_player => {
+                    1.0 - root_player_reward
+                }
+                _ => root_player_reward,
+            };
+        }
+    }
+
+    // plays random moves for a while and returns how likely the root player is to win from there
+    fn playout(&mut self, mut game_state: GameState, root_player: Player) -> f64 {
+        for _ in 0..self.config.playout_depth {
+            let player = game_state.next_player_to_act();
+            let Some(user_action_with_player) = game_state
+                .legal_moves(player)
+                .choose(&mut self.rng)
+                .cloned()
+            else {
+                break;
+            };
+            game_state
+                .receive_user_action(user_action_with_player)
+                .unwrap();
+        }
+        match game_state.winner() {
+            Some(winner) if winner == root_player => 1.0,
+            Some(_) => 0.0,
+            None => {
+                let evaluation = evaluate(&game_state, root_player).total();
+                1.0 / (1.0 + (-evaluation / EVALUATION_SCALE).exp())
+            }
+        }
+    }
+}
+
+// reshuffles everything the player can't see: the opponent's hand, the deck and future shuffles
+fn determinize(game_state: &GameState, player: Player, rng: &mut impl Rng) -> GameState {
+    let mut game_state = game_state.clone();
+    game_state.rng = GameRng::seed_from_u64(rng.gen());
+    let opponent = player.other_player();
+    let (opponent_hand, deck) = match opponent {
+        Player::Player1 => (&mut game_state.player1.hand, &mut game_state.deck),
+        Player::Player2 => (&mut game_state.player2.hand, &mut game_state.deck),
+    };
+    let opponent_hand_size = opponent_hand.len();
+    let mut unseen_cards: Vec<_> = opponent_hand.drain(..).chain(deck.drain(..)).collect();
+    unseen_cards.shuffle(rng);
+    *deck = unseen_cards.split_off(opponent_hand_size);
+    *opponent_hand = unseen_cards;
+    game_state
+}
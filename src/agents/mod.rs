@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use crate::game::{GameState, Player, UserActionWithPlayer};
+
+mod greedy;
+mod mcts;
+mod random;
+
+pub use greedy::GreedyAgent;
+pub use mcts::{MctsAgent, MctsConfig, Strength};
+pub use random::RandomAgent;
+
+pub trait Agent: Send {
+    // legal_moves is never empty, and the chosen move should be one of them
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player: Player,
+        legal_moves: &[UserActionWithPlayer],
+    ) -> UserActionWithPlayer;
+}
+
+// asks the agent for a move if the game is waiting on this player
+pub fn choose_user_action(
+    agent: &mut dyn Agent,
+    game_state: &GameState,
+    player: Player,
+) -> Option<UserActionWithPlayer> {
+    let legal_moves = game_state.legal_moves(player);
+    if legal_moves.is_empty() {
+        return None;
+    }
+    Some(agent.choose_action(game_state, player, &legal_moves))
+}
+
+pub type AgentFactory = fn(seed: u64) -> Box<dyn Agent>;
+
+pub struct AgentRegistry {
+    factories: BTreeMap<String, AgentFactory>,
+}
+
+impl AgentRegistry {
+    pub fn empty() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, name: &str, factory: AgentFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    pub fn create(&self, name: &str, seed: u64) -> Option<Box<dyn Agent>> {
+        self.factories.get(name).map(|factory| factory(seed))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+}
+
+impl Default for AgentRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("random", |seed| Box::new(RandomAgent::new(seed)));
+        registry.register("greedy", |seed| Box::new(GreedyAgent::new(seed)));
+        registry.register("mcts-easy", |seed| {
+            Box::new(MctsAgent::new(
+                MctsConfig::with_strength(Strength::Easy),
+                seed,
+            ))
+        });
+        registry.register("mcts", |seed| {
+            Box::new(MctsAgent::new(
+                MctsConfig::with_strength(Strength::Medium),
+                seed,
+            ))
+        });
+        registry.register("mcts-hard", |seed| {
+            Box::new(MctsAgent::new(
+                MctsConfig::with_strength(Strength::Hard),
+                seed,
+            ))
+        });
+        registry
+    }
+}
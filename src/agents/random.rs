@@ -0,0 +1,27 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::agents::Agent;
+use crate::game::{GameState, Player, UserActionWithPlayer};
+
+pub struct RandomAgent {
+    rng: StdRng,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn choose_action(
+        &mut self,
+        _game_state: &GameState,
+        _player: Player,
+        legal_moves: &[UserActionWithPlayer],
+    ) -> UserActionWithPlayer {
+        legal_moves.choose(&mut self.rng).unwrap().clone()
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::cards::get_deck;
+use crate::simulation::GameRecord;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CardUsage {
+    plays: u64,
+    plays_in_won_games: u64,
+    plays_by_winner: u64,
+    total_impact: f64,
+}
+
+impl CardUsage {
+    fn win_rate(&self) -> Option<f64> {
+        ratio(self.plays_by_winner, self.plays_in_won_games)
+    }
+
+    fn average_impact(&self) -> Option<f64> {
+        (self.plays > 0).then(|| self.total_impact / self.plays as f64)
+    }
+}
+
+// how the cards were used across simulated games, keyed by card name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CardAnalytics {
+    instant: BTreeMap<String, CardUsage>,
+    hot_wire: BTreeMap<String, CardUsage>,
+}
+
+impl CardAnalytics {
+    pub fn add(&mut self, record: &GameRecord) {
+        for card_play in &record.card_plays {
+            let usages = if card_play.hot_wire {
+                &mut self.hot_wire
+            } else {
+                &mut self.instant
+            };
+            let usage = usages.entry(card_play.card.clone()).or_default();
+            usage.plays += 1;
+            usage.total_impact += card_play.impact;
+            if let Some(winner) = record.winner {
+                usage.plays_in_won_games += 1;
+                if winner == card_play.player {
+                    usage.plays_by_winner += 1;
+                }
+            }
+        }
+    }
+
+    // one row for every card in the deck, played or not
+    pub fn report(&self) -> Vec<CardReport> {
+        let mut cards = BTreeMap::new();
+        for card in get_deck() {
+            cards.entry(card.name.clone()).or_insert((card, 0)).1 += 1;
+        }
+        cards
+            .into_values()
+            .map(|(card, copies)| {
+                let instant = self.instant.get(&card.name).cloned().unwrap_or_default();
+                let hot_wire = self.hot_wire.get(&card.name).cloned().unwrap_or_default();
+                CardReport {
+                    copies,
+                    hot_wire_short_circuits: card.hot_wire_cost.short_circuits,
+                    hot_wire_cards_to_discard: card.hot_wire_cost.cards_to_discard,
+                    instant_plays: instant.plays,
+                    hot_wire_plays: hot_wire.plays,
+                    hot_wire_share: ratio(hot_wire.plays, instant.plays + hot_wire.plays),
+                    instant_win_rate: instant.win_rate(),
+                    hot_wire_win_rate: hot_wire.win_rate(),
+                    instant_impact: instant.average_impact(),
+                    hot_wire_impact: hot_wire.average_impact(),
+                    card: card.name.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CardReport {
+    pub card: String,
+    pub copies: usize,
+    pub hot_wire_short_circuits: i32,
+    pub hot_wire_cards_to_discard: usize,
+    pub instant_plays: u64,
+    pub hot_wire_plays: u64,
+    pub hot_wire_share: Option<f64>,
+    // how often the player who played the card went on to win, out of games someone won
+    pub instant_win_rate: Option<f64>,
+    pub hot_wire_win_rate: Option<f64>,
+    // average change in the player's evaluation from playing the card, see CardPlay::impact
+    pub instant_impact: Option<f64>,
+    pub hot_wire_impact: Option<f64>,
+}
+
+pub fn card_report_json(reports: &[CardReport]) -> String {
+    serde_json::to_string_pretty(reports).unwrap() + "\n"
+}
+
+pub fn card_report_csv(reports: &[CardReport]) -> String {
+    let mut csv = "card,copies,hot_wire_short_circuits,hot_wire_cards_to_discard,instant_plays,\
+        hot_wire_plays,hot_wire_share,instant_win_rate,hot_wire_win_rate,instant_impact,\
+        hot_wire_impact\n"
+        .to_string();
+    let optional = |value: Option<f64>| value.map_or(String::new(), |value| format!("{value:.4}"));
+    for report in reports {
+        csv += &format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            report.card,
+            report.copies,
+            report.hot_wire_short_circuits,
+            report.hot_wire_cards_to_discard,
+            report.instant_plays,
+            report.hot_wire_plays,
+            optional(report.hot_wire_share),
+            optional(report.instant_win_rate),
+            optional(report.hot_wire_win_rate),
+            optional(report.instant_impact),
+            optional(report.hot_wire_impact),
+        );
+    }
+    csv
+}
+
+fn ratio(count: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| count as f64 / total as f64)
+}
@@ -0,0 +1,70 @@
+use std::process::exit;
+
+use shields_up_engineering::{agents::AgentRegistry, client::GameClient, game::Player};
+
+const DEFAULT_AGENT: &str = "greedy";
+
+const USAGE: &str =
+    "usage: bot <server url> <game name> <player1|player2> [--agent NAME] [--seed N]";
+
+#[rocket::main]
+async fn main() {
+    let agent_registry = AgentRegistry::default();
+    let (server_url, game_name, player, agent, seed) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}");
+            eprintln!("{USAGE}");
+            exit(2);
+        }
+    };
+    let Some(mut agent) = agent_registry.create(&agent, seed) else {
+        eprintln!("unknown agent {agent}");
+        eprintln!(
+            "agents: {}",
+            agent_registry.names().collect::<Vec<_>>().join(", ")
+        );
+        exit(2);
+    };
+    let result = match GameClient::connect(&server_url, &game_name).await {
+        Ok(mut client) => client.play(agent.as_mut(), player).await,
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(winner) if winner == player => println!("won"),
+        Ok(_) => println!("lost"),
+        Err(error) => {
+            eprintln!("{error}");
+            exit(1);
+        }
+    }
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(String, String, Player, String, u64), String> {
+    let mut positional = vec![];
+    let mut agent = DEFAULT_AGENT.to_string();
+    let mut seed = rand::random();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--agent" => agent = value()?,
+            "--seed" => {
+                let value = value()?;
+                seed = value
+                    .parse()
+                    .map_err(|_| format!("invalid number {value}"))?;
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => positional.push(arg),
+        }
+    }
+    let [server_url, game_name, player]: [String; 3] = positional
+        .try_into()
+        .map_err(|_| "expected a server url, a game name and a player".to_string())?;
+    Ok((server_url, game_name, player.parse()?, agent, seed))
+}
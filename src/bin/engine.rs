@@ -0,0 +1,127 @@
+use std::process::exit;
+use std::time::Duration;
+
+use shields_up_engineering::{
+    agents::AgentRegistry,
+    engine::{run_game, EngineBot, ExternalBot, GameOutcome, DEFAULT_REPLY_TIMEOUT},
+    game::{GameState, Player},
+    simulation::DEFAULT_MAX_TURNS,
+};
+
+const USAGE: &str =
+    "usage: engine <player1 bot> <player2 bot> [--games N] [--seed N] [--max-turns N] [--timeout-ms N]
+a bot is either the name of a built in agent or a shell command that starts one, which forfeits
+if it takes longer than the timeout to reply";
+
+struct Args {
+    bots: [String; 2],
+    games: u64,
+    seed: u64,
+    max_turns: u32,
+    reply_timeout: Duration,
+}
+
+fn main() {
+    let agent_registry = AgentRegistry::default();
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}");
+            eprintln!("{USAGE}");
+            eprintln!(
+                "agents: {}",
+                agent_registry.names().collect::<Vec<_>>().join(", ")
+            );
+            exit(2);
+        }
+    };
+    let mut wins = [0; 2];
+    let mut draws = 0;
+    for game in 0..args.games {
+        let seed = args.seed.wrapping_add(game);
+        let mut bots = [
+            start_bot(&agent_registry, &args.bots[0], seed, args.reply_timeout),
+            start_bot(&agent_registry, &args.bots[1], seed, args.reply_timeout),
+        ];
+        let game_state = GameState::start_state_with_seed(seed);
+        let outcome = run_game(game_state, &mut bots, args.max_turns);
+        match &outcome {
+            GameOutcome::Won { winner, turns } => {
+                println!("game {game}: {winner:?} won after {turns} turns");
+            }
+            GameOutcome::Draw { turns } => println!("game {game}: draw after {turns} turns"),
+            GameOutcome::Forfeit { player, error } => {
+                println!("game {game}: {player:?} forfeited, {error}");
+            }
+        }
+        match outcome {
+            GameOutcome::Won { winner, .. } => wins[index(winner)] += 1,
+            GameOutcome::Forfeit { player, .. } => wins[index(player.other_player())] += 1,
+            GameOutcome::Draw { .. } => draws += 1,
+        }
+    }
+    println!(
+        "player1 ({}) wins: {}, player2 ({}) wins: {}, draws: {draws}",
+        args.bots[0], wins[0], args.bots[1], wins[1]
+    );
+}
+
+fn start_bot(
+    agent_registry: &AgentRegistry,
+    bot: &str,
+    seed: u64,
+    reply_timeout: Duration,
+) -> EngineBot {
+    if let Some(agent) = agent_registry.create(bot, seed) {
+        return EngineBot::Agent(agent);
+    }
+    match ExternalBot::spawn(bot) {
+        Ok(bot) => EngineBot::External(bot.with_reply_timeout(reply_timeout)),
+        Err(error) => {
+            eprintln!("failed to start {bot}: {error}");
+            exit(1);
+        }
+    }
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::Player1 => 0,
+        Player::Player2 => 1,
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut bots = vec![];
+    let mut parsed = Args {
+        bots: Default::default(),
+        games: 1,
+        seed: rand::random(),
+        max_turns: DEFAULT_MAX_TURNS,
+        reply_timeout: DEFAULT_REPLY_TIMEOUT,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--games" => parsed.games = parse_number(&value()?)?,
+            "--seed" => parsed.seed = parse_number(&value()?)?,
+            "--max-turns" => parsed.max_turns = parse_number(&value()?)?,
+            "--timeout-ms" => {
+                parsed.reply_timeout = Duration::from_millis(parse_number(&value()?)?)
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => bots.push(arg),
+        }
+    }
+    parsed.bots = bots
+        .try_into()
+        .map_err(|_| "expected exactly two bots".to_string())?;
+    Ok(parsed)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid number {value}"))
+}
@@ -0,0 +1,153 @@
+use std::io::{self, BufRead, Write};
+use std::process::exit;
+
+use rocket::tokio::task::spawn_blocking;
+use shields_up_engineering::{
+    client::{ClientError, GameClient},
+    display::{describe_user_action, menu_group, render_game_state},
+    game::{Player, UserActionWithPlayer},
+    protocol::ServerMessage,
+};
+
+const USAGE: &str = "usage: play <server url> <game name> <player1|player2>";
+const HINT_COUNT: usize = 5;
+
+#[rocket::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [server_url, game_name, player] = args.as_slice() else {
+        eprintln!("{USAGE}");
+        exit(2);
+    };
+    let player: Player = match player.parse() {
+        Ok(player) => player,
+        Err(error) => {
+            eprintln!("{error}");
+            eprintln!("{USAGE}");
+            exit(2);
+        }
+    };
+    let result = match GameClient::connect(server_url, game_name).await {
+        Ok(mut client) => play(&mut client, player).await,
+        Err(error) => Err(error),
+    };
+    if let Err(error) = result {
+        eprintln!("{error}");
+        exit(1);
+    }
+}
+
+async fn play(client: &mut GameClient, me: Player) -> Result<(), ClientError> {
+    let mut shown_version = None;
+    loop {
+        let game_state = client.game_state().clone();
+        if shown_version != Some(client.version()) {
+            println!("\n{}", render_game_state(&game_state, me));
+            shown_version = Some(client.version());
+        }
+        if game_state.winner().is_some() {
+            return Ok(());
+        }
+        if !game_state.is_waiting_on(me) {
+            println!("waiting for the opponent...");
+            match client.next_message().await? {
+                ServerMessage::Event { event } => println!("{event:?}"),
+                ServerMessage::Error { error, .. } => println!("server error: {error:?}"),
+                _ => {}
+            }
+            continue;
+        }
+        let Some(user_action_with_player) = choose_move(client, me).await? else {
+            return Ok(());
+        };
+        if let Err(error) = client.act(user_action_with_player).await? {
+            println!("not allowed: {error:?}");
+        }
+    }
+}
+
+// walks the player through the menus, None if they want to quit
+async fn choose_move(
+    client: &mut GameClient,
+    me: Player,
+) -> Result<Option<UserActionWithPlayer>, ClientError> {
+    let game_state = client.game_state().clone();
+    let mut groups: Vec<(String, Vec<UserActionWithPlayer>)> = vec![];
+    for user_action_with_player in game_state.legal_moves(me) {
+        let group = menu_group(&user_action_with_player.user_action);
+        match groups.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, moves)) => moves.push(user_action_with_player),
+            None => groups.push((group, vec![user_action_with_player])),
+        }
+    }
+    loop {
+        for (index, (group, moves)) in groups.iter().enumerate() {
+            let options = if moves.len() > 1 {
+                format!(" ({} options)", moves.len())
+            } else {
+                String::new()
+            };
+            println!("{}) {group}{options}", index + 1);
+        }
+        println!("h) hints  q) quit");
+        let Some(input) = prompt("> ").await else {
+            return Ok(None);
+        };
+        match input.as_str() {
+            "q" => return Ok(None),
+            "h" => {
+                for hint in client.hints(me, HINT_COUNT).await? {
+                    println!(
+                        "{:+.1} {}: {}",
+                        hint.score,
+                        describe_user_action(&game_state, &hint.user_action_with_player),
+                        hint.rationale
+                    );
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let Some((_, moves)) = pick(&input, &groups) else {
+            println!("pick one of the numbers above");
+            continue;
+        };
+        if let [user_action_with_player] = moves.as_slice() {
+            return Ok(Some(user_action_with_player.clone()));
+        }
+        for (index, user_action_with_player) in moves.iter().enumerate() {
+            println!(
+                "{}) {}",
+                index + 1,
+                describe_user_action(&game_state, user_action_with_player)
+            );
+        }
+        println!("b) back");
+        let Some(input) = prompt("> ").await else {
+            return Ok(None);
+        };
+        if let Some(user_action_with_player) = pick(&input, moves) {
+            return Ok(Some(user_action_with_player.clone()));
+        }
+    }
+}
+
+fn pick<'a, T>(input: &str, options: &'a [T]) -> Option<&'a T> {
+    let index: usize = input.parse().ok()?;
+    options.get(index.checked_sub(1)?)
+}
+
+// None once stdin is closed
+async fn prompt(text: &str) -> Option<String> {
+    print!("{text}");
+    io::stdout().flush().unwrap();
+    spawn_blocking(|| {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    })
+    .await
+    .unwrap()
+}
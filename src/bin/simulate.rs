@@ -0,0 +1,177 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    process::exit,
+    sync::Mutex,
+    thread::available_parallelism,
+};
+
+use shields_up_engineering::{
+    agents::AgentRegistry,
+    analytics::{card_report_csv, card_report_json},
+    simulation::{simulate_with, SimulationConfig, SimulationSummary, DEFAULT_MAX_TURNS},
+};
+
+const DEFAULT_GAMES: usize = 100;
+
+const USAGE: &str =
+    "usage: simulate <agent> <agent> [--games N] [--seed N] [--threads N] [--max-turns N] \
+    [--card-report FILE.csv|FILE.json] [--export FILE.jsonl]";
+
+struct Args {
+    config: SimulationConfig,
+    card_report: Option<String>,
+    export: Option<String>,
+}
+
+fn main() {
+    let agent_registry = AgentRegistry::default();
+    let Args {
+        config,
+        card_report,
+        export,
+    } = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}");
+            eprintln!("{USAGE}");
+            eprintln!(
+                "agents: {}",
+                agent_registry.names().collect::<Vec<_>>().join(", ")
+            );
+            exit(2);
+        }
+    };
+    let export = export.map(|path| match File::create(&path) {
+        Ok(file) => (path, Mutex::new(BufWriter::new(file))),
+        Err(error) => {
+            eprintln!("failed to create {path}: {error}");
+            exit(1);
+        }
+    });
+    let summary = simulate_with(
+        &config,
+        &agent_registry,
+        |game, record, first_agent_player| {
+            let Some((path, writer)) = &export else {
+                return;
+            };
+            let jsonl = record.training_examples_jsonl(game, &config.agents, first_agent_player);
+            if let Err(error) = writer.lock().unwrap().write_all(jsonl.as_bytes()) {
+                eprintln!("failed to write {path}: {error}");
+                exit(1);
+            }
+        },
+    );
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(error) => {
+            eprintln!("{error}");
+            exit(2);
+        }
+    };
+    if let Some((path, writer)) = export {
+        if let Err(error) = writer.into_inner().unwrap().flush() {
+            eprintln!("failed to write {path}: {error}");
+            exit(1);
+        }
+    }
+    print_summary(&config, &summary);
+    if let Some(path) = card_report {
+        let reports = summary.card_analytics.report();
+        let contents = if path.ends_with(".json") {
+            card_report_json(&reports)
+        } else {
+            card_report_csv(&reports)
+        };
+        if let Err(error) = fs::write(&path, contents) {
+            eprintln!("failed to write {path}: {error}");
+            exit(1);
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut agents = vec![];
+    let mut card_report = None;
+    let mut export = None;
+    let mut config = SimulationConfig {
+        agents: Default::default(),
+        games: DEFAULT_GAMES,
+        seed: rand::random(),
+        threads: available_parallelism().map_or(1, |threads| threads.get()),
+        max_turns: DEFAULT_MAX_TURNS,
+        record_decisions: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--games" => config.games = parse_number(&value()?)?,
+            "--seed" => config.seed = parse_number(&value()?)?,
+            "--threads" => config.threads = parse_number(&value()?)?,
+            "--max-turns" => config.max_turns = parse_number(&value()?)?,
+            "--card-report" => card_report = Some(value()?),
+            "--export" => export = Some(value()?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => agents.push(arg),
+        }
+    }
+    config.agents = agents
+        .try_into()
+        .map_err(|_| "expected exactly two agents".to_string())?;
+    config.record_decisions = export.is_some();
+    Ok(Args {
+        config,
+        card_report,
+        export,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid number {value}"))
+}
+
+fn print_summary(config: &SimulationConfig, summary: &SimulationSummary) {
+    let percent = |count: u32| 100.0 * summary.rate(count as u64);
+    println!(
+        "{} games of {} vs {} with seed {}",
+        summary.games, config.agents[0], config.agents[1], config.seed
+    );
+    for (agent, wins) in config.agents.iter().zip(summary.wins) {
+        println!("{agent}: {wins} wins ({:.1}%)", percent(wins));
+    }
+    println!(
+        "draws after {} turns: {} ({:.1}%)",
+        config.max_turns,
+        summary.draws,
+        percent(summary.draws)
+    );
+    println!(
+        "player 1 wins: {} ({:.1}%)",
+        summary.player1_wins,
+        percent(summary.player1_wins)
+    );
+    println!(
+        "average game: {:.1} turns, {:.1} user actions",
+        summary.rate(summary.total_turns),
+        summary.rate(summary.total_user_actions)
+    );
+    print_counts("actions", summary, &summary.action_counts);
+    print_counts("effects", summary, &summary.effect_counts);
+}
+
+fn print_counts(title: &str, summary: &SimulationSummary, counts: &BTreeMap<String, u64>) {
+    let total: u64 = counts.values().sum();
+    println!("{title}:");
+    for (name, &count) in counts {
+        println!(
+            "  {name:<24} {count:>8} ({:.1}%, {:.1} per game)",
+            100.0 * count as f64 / total as f64,
+            summary.rate(count)
+        );
+    }
+}
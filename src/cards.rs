@@ -1,48 +1,110 @@
-use rand::{seq::SliceRandom, thread_rng};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
 
-use crate::game::{Card, Effect, HotWireCost, System};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+
+use crate::game::{Card, CardDefinition, CardId, Effect, HotWireCost, System};
+
+const COPIES_IN_DECK: usize = 3;
+
+// every card in the game, states only refer to these
+struct Registry {
+    // sorted by name
+    definitions: Vec<Arc<CardDefinition>>,
+    by_name: BTreeMap<String, usize>,
+    // keyed by the address of each shared definition, so finding a card's position is cheap
+    by_address: BTreeMap<usize, usize>,
+}
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut definitions: Vec<Arc<CardDefinition>> = [
+            attack_01(),
+            attack_02(),
+            draw_01(),
+            draw_06(),
+            generic_01(),
+            generic_02(),
+            generic_07(),
+            power_05b(),
+            shields_01(),
+            shields_02(),
+        ]
+        .into_iter()
+        .map(Arc::new)
+        .collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        let by_name = definitions
+            .iter()
+            .enumerate()
+            .map(|(position, definition)| (definition.name.clone(), position))
+            .collect();
+        let by_address = definitions
+            .iter()
+            .enumerate()
+            .map(|(position, definition)| (Arc::as_ptr(definition) as usize, position))
+            .collect();
+        Registry {
+            definitions,
+            by_name,
+            by_address,
+        }
+    })
+}
+
+pub fn card_definition(name: &str) -> Option<&'static Arc<CardDefinition>> {
+    let registry = registry();
+    registry
+        .by_name
+        .get(name)
+        .map(|&position| &registry.definitions[position])
+}
+
+// sorted by name
+pub fn card_definitions() -> impl Iterator<Item = &'static Arc<CardDefinition>> {
+    registry().definitions.iter()
+}
+
+// the position of the definition in card_definitions(), if it is one of them: either shared
+// with the registry or a copy equal to it in everything, a definition that only shares a name
+// with a registry card isn't that card
+pub fn card_definition_position(definition: &Arc<CardDefinition>) -> Option<usize> {
+    let registry = registry();
+    if let Some(&position) = registry.by_address.get(&(Arc::as_ptr(definition) as usize)) {
+        return Some(position);
+    }
+    registry
+        .by_name
+        .get(&definition.name)
+        .copied()
+        .filter(|&position| *registry.definitions[position] == **definition)
+}
+
+pub fn card_definition_at(position: usize) -> Option<&'static Arc<CardDefinition>> {
+    registry().definitions.get(position)
+}
 
 pub fn get_deck() -> Vec<Card> {
-    let mut deck = vec![
-        attack_01(),
-        attack_02(),
-        draw_01(),
-        draw_06(),
-        generic_01(),
-        generic_02(),
-        generic_07(),
-        power_05b(),
-        shields_01(),
-        shields_02(),
-        // copy 2
-        attack_01(),
-        attack_02(),
-        draw_01(),
-        draw_06(),
-        generic_01(),
-        generic_02(),
-        generic_07(),
-        power_05b(),
-        shields_01(),
-        shields_02(),
-        // copy 3
-        attack_01(),
-        attack_02(),
-        draw_01(),
-        draw_06(),
-        generic_01(),
-        generic_02(),
-        generic_07(),
-        power_05b(),
-        shields_01(),
-        shields_02(),
-    ];
-    deck.shuffle(&mut thread_rng());
+    get_deck_with_rng(&mut thread_rng())
+}
+
+// ids are handed out before shuffling, so a card's id doesn't depend on the seed
+pub fn get_deck_with_rng(rng: &mut impl Rng) -> Vec<Card> {
+    let mut deck: Vec<Card> = (0..COPIES_IN_DECK)
+        .flat_map(|_| card_definitions())
+        .enumerate()
+        .map(|(id, definition)| Card {
+            id: CardId(id as u32),
+            definition: Arc::clone(definition),
+        })
+        .collect();
+    deck.shuffle(rng);
     deck
 }
 
-fn attack_01() -> Card {
-    Card {
+fn attack_01() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::Attack],
         hot_wire_effects: vec![Effect::Attack, Effect::UseMoreEnergy],
         hot_wire_cost: HotWireCost {
@@ -54,8 +116,8 @@ fn attack_01() -> Card {
     }
 }
 
-fn attack_02() -> Card {
-    Card {
+fn attack_02() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::Attack],
         hot_wire_effects: vec![
             Effect::Attack,
@@ -74,8 +136,8 @@ fn attack_02() -> Card {
     }
 }
 
-fn draw_01() -> Card {
-    Card {
+fn draw_01() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![
             Effect::Draw,
             Effect::Draw,
@@ -92,8 +154,8 @@ fn draw_01() -> Card {
     }
 }
 
-fn draw_06() -> Card {
-    Card {
+fn draw_06() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::GainAction, Effect::LoseShortCircuit],
         hot_wire_effects: vec![Effect::OpponentGainShortCircuit],
         hot_wire_cost: HotWireCost {
@@ -105,8 +167,8 @@ fn draw_06() -> Card {
     }
 }
 
-fn generic_01() -> Card {
-    Card {
+fn generic_01() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::GainAction, Effect::LoseShortCircuit],
         hot_wire_effects: vec![Effect::UseLessEnergy],
         hot_wire_cost: HotWireCost {
@@ -118,8 +180,8 @@ fn generic_01() -> Card {
     }
 }
 
-fn generic_02() -> Card {
-    Card {
+fn generic_02() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::GainAction, Effect::LoseShortCircuit],
         hot_wire_effects: vec![Effect::LoseShortCircuit],
         hot_wire_cost: HotWireCost {
@@ -131,8 +193,8 @@ fn generic_02() -> Card {
     }
 }
 
-fn generic_07() -> Card {
-    Card {
+fn generic_07() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![
             Effect::LoseShortCircuit,
             Effect::LoseShortCircuit,
@@ -149,8 +211,8 @@ fn generic_07() -> Card {
     }
 }
 
-fn power_05b() -> Card {
-    Card {
+fn power_05b() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::GainAction, Effect::LoseShortCircuit],
         hot_wire_effects: vec![Effect::LoseShortCircuit],
         hot_wire_cost: HotWireCost {
@@ -162,8 +224,8 @@ fn power_05b() -> Card {
     }
 }
 
-fn shields_01() -> Card {
-    Card {
+fn shields_01() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::Shield, Effect::LoseShortCircuit],
         hot_wire_effects: vec![Effect::Shield, Effect::UseMoreEnergy],
         hot_wire_cost: HotWireCost {
@@ -175,8 +237,8 @@ fn shields_01() -> Card {
     }
 }
 
-fn shields_02() -> Card {
-    Card {
+fn shields_02() -> CardDefinition {
+    CardDefinition {
         instant_effects: vec![Effect::Shield, Effect::LoseShortCircuit],
         hot_wire_effects: vec![
             Effect::Shield,
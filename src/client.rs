@@ -1,171 +1,308 @@
-#![allow(dead_code)]
+use std::collections::VecDeque;
+use std::fmt;
 
-use std::collections::BTreeMap;
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream, WebSocketStream};
 
-use rand::Rng;
+use crate::agents::{choose_user_action, Agent};
+use crate::game::{GameState, Player, UserActionError, UserActionWithPlayer};
+use crate::hints::Hint;
+use crate::protocol::{ClientMessage, ProtocolError, ServerMessage, PROTOCOL_VERSION};
 
-use crate::game::*;
+#[derive(Debug)]
+pub enum ClientError {
+    WebSocket(tungstenite::Error),
+    Json(serde_json::Error),
+    Protocol(ProtocolError),
+    ConnectionClosed,
+    UnexpectedMessage(Box<ServerMessage>),
+}
 
-fn random_option(options: usize) -> usize {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(0..options)
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::WebSocket(error) => write!(f, "websocket error: {error}"),
+            ClientError::Json(error) => write!(f, "invalid json: {error}"),
+            ClientError::Protocol(error) => write!(f, "server reported an error: {error:?}"),
+            ClientError::ConnectionClosed => write!(f, "connection closed"),
+            ClientError::UnexpectedMessage(message) => {
+                write!(f, "unexpected message from server: {message:?}")
+            }
+        }
+    }
 }
 
-fn random_system() -> System {
-    match random_option(4) {
-        0 => System::FusionReactor,
-        1 => System::ShieldGenerator,
-        2 => System::LifeSupport,
-        3 => System::Weapons,
-        _ => panic!(),
+impl std::error::Error for ClientError {}
+
+impl From<tungstenite::Error> for ClientError {
+    fn from(error: tungstenite::Error) -> Self {
+        ClientError::WebSocket(error)
     }
 }
 
-pub fn get_user_action(game_state: &GameState) -> UserActionWithPlayer {
-    let player = game_state.players_turn;
-    let my_state = game_state.my_state_immut(player);
-    let user_action = match &game_state.turn_state {
-        TurnState::ChoosingAction => {
-            if game_state.actions_left == 0 {
-                let mut card_indices_to_discard = vec![];
-                if my_state.hand.len() > 5 {
-                    card_indices_to_discard = (0..(my_state.hand.len() - 5)).collect();
+impl From<serde_json::Error> for ClientError {
+    fn from(error: serde_json::Error) -> Self {
+        ClientError::Json(error)
+    }
+}
+
+// a connection to one game on the server, keeping an up to date copy of the game state
+pub struct GameClient {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    game_state: GameState,
+    game_state_json: serde_json::Value,
+    version: u64,
+    next_request_id: u64,
+    // messages that arrived while waiting for the reply to a request, state changes are applied
+    // straight away so they aren't kept here
+    unread_messages: VecDeque<ServerMessage>,
+}
+
+impl GameClient {
+    // server_url is the server's base url, e.g. ws://localhost:8000
+    pub async fn connect(server_url: &str, game_name: &str) -> Result<Self, ClientError> {
+        let url = format!(
+            "{}/game/{game_name}?mode=patch",
+            server_url.trim_end_matches('/')
+        );
+        let (mut stream, _) = connect_async(url).await?;
+        send(
+            &mut stream,
+            &ClientMessage::Hello {
+                protocol_version: PROTOCOL_VERSION,
+            },
+        )
+        .await?;
+        match receive(&mut stream).await? {
+            ServerMessage::Welcome { .. } => {}
+            ServerMessage::Error { error, .. } => return Err(ClientError::Protocol(error)),
+            message => return Err(ClientError::UnexpectedMessage(Box::new(message))),
+        }
+        // the server follows the welcome with a snapshot, possibly after some events
+        let mut unread_messages = VecDeque::new();
+        loop {
+            match receive(&mut stream).await? {
+                ServerMessage::State {
+                    version,
+                    game_state,
+                } => {
+                    return Ok(Self {
+                        stream,
+                        game_state_json: serde_json::to_value(&game_state)?,
+                        game_state: *game_state,
+                        version,
+                        next_request_id: 0,
+                        unread_messages,
+                    });
                 }
-                return UserActionWithPlayer {
-                    player,
-                    user_action: UserAction::Pass {
-                        card_indices_to_discard,
-                    },
-                };
+                message => unread_messages.push_back(message),
             }
-            let action = match random_option(5) {
-                0 => {
-                    let system = random_system();
-                    let mut energy_distribution = None;
-                    if system == System::FusionReactor {
-                        let mut dist = BTreeMap::new();
-                        let total = my_state.fusion_reactor.get_allowed_energy();
-                        dist.insert(System::Weapons, 2);
-                        dist.insert(System::LifeSupport, 2);
-                        dist.insert(System::ShieldGenerator, 1);
-                        dist.insert(System::FusionReactor, total - 5);
-                        energy_distribution = Some(dist);
-                    }
-                    Action::ActivateSystem {
-                        system,
-                        energy_to_use: None,
-                        energy_distribution,
-                    }
-                }
-                1 => Action::DiscardOverload {
-                    system: random_system(),
-                },
-                2 => {
-                    if my_state.hand.is_empty() {
-                        return get_user_action(game_state);
+        }
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    // waits for the next message from the server, applying any state change it carries
+    pub async fn next_message(&mut self) -> Result<ServerMessage, ClientError> {
+        match self.unread_messages.pop_front() {
+            Some(message) => Ok(message),
+            None => self.receive().await,
+        }
+    }
+
+    async fn receive(&mut self) -> Result<ServerMessage, ClientError> {
+        let message = receive(&mut self.stream).await?;
+        match &message {
+            ServerMessage::State {
+                version,
+                game_state,
+            } => {
+                self.game_state_json = serde_json::to_value(game_state)?;
+                self.game_state = (**game_state).clone();
+                self.version = *version;
+            }
+            ServerMessage::Patch { version, patch } => {
+                let mut game_state_json = self.game_state_json.clone();
+                let applied = *version == self.version + 1
+                    && json_patch::patch(&mut game_state_json, patch).is_ok();
+                match serde_json::from_value(game_state_json.clone()) {
+                    Ok(game_state) if applied => {
+                        self.game_state = game_state;
+                        self.game_state_json = game_state_json;
+                        self.version = *version;
                     }
-                    let card = my_state.hand.first().unwrap();
-                    let cards_to_discard = card.hot_wire_cost.cards_to_discard;
-                    Action::HotWireCard {
-                        card_index: 0,
-                        system: random_system(),
-                        indices_to_discard: (1..=cards_to_discard).collect(),
+                    // missed an update somewhere, a full snapshot will follow
+                    _ => {
+                        self.send(ClientMessage::Resync { request_id: None })
+                            .await?
                     }
                 }
-                3 => {
-                    if my_state.hand.is_empty() {
-                        return get_user_action(game_state);
-                    };
-                    Action::PlayInstantCard { card_index: 0 }
+            }
+            _ => {}
+        }
+        Ok(message)
+    }
+
+    // sends an action without waiting for the result, returning the request id
+    pub async fn send_action(
+        &mut self,
+        user_action_with_player: UserActionWithPlayer,
+    ) -> Result<u64, ClientError> {
+        let request_id = self.next_request_id();
+        self.send(ClientMessage::Action {
+            request_id: Some(request_id),
+            user_action_with_player,
+        })
+        .await?;
+        Ok(request_id)
+    }
+
+    // sends an action and waits until the result is known and, if it was accepted, until the
+    // state it led to has arrived
+    pub async fn act(
+        &mut self,
+        user_action_with_player: UserActionWithPlayer,
+    ) -> Result<Result<(), UserActionError>, ClientError> {
+        let request_id = self.send_action(user_action_with_player).await?;
+        let (version, result) = self
+            .wait_for_reply(request_id, |message| match message {
+                ServerMessage::ActionResult {
+                    version, result, ..
+                } => Ok((version, result)),
+                message => Err(message),
+            })
+            .await?;
+        while self.version < version {
+            let message = self.receive().await?;
+            self.keep_unread(message);
+        }
+        Ok(result)
+    }
+
+    pub async fn hints(&mut self, player: Player, count: usize) -> Result<Vec<Hint>, ClientError> {
+        let request_id = self.next_request_id();
+        self.send(ClientMessage::Hint {
+            request_id: Some(request_id),
+            player,
+            count: Some(count),
+        })
+        .await?;
+        self.wait_for_reply(request_id, |message| match message {
+            ServerMessage::Hints { hints, .. } => Ok(hints),
+            message => Err(message),
+        })
+        .await
+    }
+
+    // lets the agent play the given seat until someone wins
+    pub async fn play(
+        &mut self,
+        agent: &mut dyn Agent,
+        player: Player,
+    ) -> Result<Player, ClientError> {
+        loop {
+            if let Some(winner) = self.game_state.winner() {
+                return Ok(winner);
+            }
+            if self.game_state.is_waiting_on(player) {
+                if let Some(user_action_with_player) =
+                    choose_user_action(agent, &self.game_state, player)
+                {
+                    // a rejected move means the game moved on meanwhile, so just look again
+                    let _ = self.act(user_action_with_player).await?;
+                    continue;
                 }
-                4 => Action::ReduceShortCircuits,
-                _ => panic!(),
-            };
-            UserAction::ChooseAction { action }
+            }
+            let message = self.next_message().await?;
+            if let ServerMessage::Error { error, .. } = message {
+                return Err(ClientError::Protocol(error));
+            }
         }
-        TurnState::ResolvingEffects { effects } => {
-            for effect in effects {
-                if let Some(resolve_effect) = effect.get_resolution(game_state) {
-                    let player = if let ResolveEffect::OpponentDiscard { .. } = resolve_effect {
-                        player.other_player()
-                    } else {
-                        player
-                    };
-                    let user_action = UserAction::ResolveEffect { resolve_effect };
-                    if game_state
-                        .clone()
-                        .receive_user_action(UserActionWithPlayer {
-                            player,
-                            user_action: user_action.clone(),
-                        })
-                        .is_ok()
-                    {
-                        return UserActionWithPlayer {
-                            player,
-                            user_action,
-                        };
-                    }
+    }
+
+    pub async fn close(mut self) -> Result<(), ClientError> {
+        self.stream.close(None).await?;
+        Ok(())
+    }
+
+    fn next_request_id(&mut self) -> u64 {
+        self.next_request_id += 1;
+        self.next_request_id
+    }
+
+    async fn send(&mut self, client_message: ClientMessage) -> Result<(), ClientError> {
+        send(&mut self.stream, &client_message).await
+    }
+
+    // reads messages until the reply to the request, keeping everything else for next_message
+    async fn wait_for_reply<T>(
+        &mut self,
+        request_id: u64,
+        reply: impl FnOnce(ServerMessage) -> Result<T, ServerMessage>,
+    ) -> Result<T, ClientError> {
+        loop {
+            match self.receive().await? {
+                ServerMessage::Error {
+                    request_id: Some(id),
+                    error,
+                } if id == request_id => return Err(ClientError::Protocol(error)),
+                message if reply_to(&message) == Some(request_id) => {
+                    return reply(message)
+                        .map_err(|message| ClientError::UnexpectedMessage(Box::new(message)));
                 }
+                message => self.keep_unread(message),
             }
-            UserAction::StopResolvingEffects
         }
-    };
-    UserActionWithPlayer {
-        player,
-        user_action,
+    }
+
+    fn keep_unread(&mut self, message: ServerMessage) {
+        if !matches!(
+            message,
+            ServerMessage::State { .. } | ServerMessage::Patch { .. }
+        ) {
+            self.unread_messages.push_back(message);
+        }
     }
 }
 
-impl Effect {
-    fn get_resolution(&self, game_state: &GameState) -> Option<ResolveEffect> {
-        let player = game_state.players_turn;
-        let my_state = game_state.my_state_immut(player);
-        match self {
-            Effect::GainShortCircuit => Some(ResolveEffect::GainShortCircuit),
-            Effect::LoseShortCircuit => Some(ResolveEffect::LoseShortCircuit),
-            Effect::Shield => Some(ResolveEffect::Shield),
-            Effect::Attack => Some(ResolveEffect::Attack),
-            Effect::DiscardOverload => Some(ResolveEffect::DiscardOverload {
-                system: random_system(),
-            }),
-            Effect::GainAction => Some(ResolveEffect::GainAction),
-            Effect::PlayHotWire => {
-                if my_state.hand.is_empty() {
-                    return None;
-                }
-                let card = my_state.hand.first().unwrap();
-                let cards_to_discard = card.hot_wire_cost.cards_to_discard;
-                Some(ResolveEffect::PlayHotWire {
-                    card_index: 0,
-                    system: random_system(),
-                    indices_to_discard: (1..=cards_to_discard).collect(),
-                })
+fn reply_to(message: &ServerMessage) -> Option<u64> {
+    match message {
+        ServerMessage::ActionResult { request_id, .. }
+        | ServerMessage::Error { request_id, .. }
+        | ServerMessage::Pong { request_id }
+        | ServerMessage::Hints { request_id, .. } => *request_id,
+        _ => None,
+    }
+}
+
+async fn send(
+    stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+    client_message: &ClientMessage,
+) -> Result<(), ClientError> {
+    let text = serde_json::to_string(client_message)?;
+    stream.send(tungstenite::Message::Text(text)).await?;
+    Ok(())
+}
+
+async fn receive(
+    stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+) -> Result<ServerMessage, ClientError> {
+    loop {
+        match stream.next().await {
+            Some(Ok(tungstenite::Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+            Some(Ok(tungstenite::Message::Close(_))) | None => {
+                return Err(ClientError::ConnectionClosed)
             }
-            Effect::Draw => Some(ResolveEffect::Draw),
-            Effect::OpponentGainShortCircuit => Some(ResolveEffect::OpponentGainShortCircuit),
-            Effect::OpponentLoseShield => Some(ResolveEffect::OpponentLoseShield),
-            Effect::OpponentMoveEnergy => Some(ResolveEffect::OpponentMoveEnergy {
-                from_system: random_system(),
-                to_system: random_system(),
-            }),
-            Effect::OpponentGainOverload => Some(ResolveEffect::OpponentGainOverload {
-                system: random_system(),
-            }),
-            Effect::MoveEnergy => Some(ResolveEffect::MoveEnergy {
-                from_system: random_system(),
-                to_system: random_system(),
-            }),
-            Effect::MoveEnergyTo(to_system) => Some(ResolveEffect::MoveEnergyTo {
-                from_system: random_system(),
-                to_system: *to_system,
-            }),
-            Effect::OpponentDiscard => Some(ResolveEffect::OpponentDiscard { card_index: 0 }),
-            Effect::BypassShield => Some(ResolveEffect::BypassShield),
-            Effect::StoreMoreEnergy
-            | Effect::UseMoreEnergy
-            | Effect::UseLessEnergy
-            | Effect::UseSystemCards(_)
-            | Effect::DrawPowerFrom(_) => None,
+            // pings are answered by tungstenite itself
+            Some(Ok(_)) => {}
+            Some(Err(error)) => return Err(error.into()),
         }
     }
 }
@@ -0,0 +1,395 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::cards::{card_definition_at, card_definition_position};
+use crate::game::*;
+
+// bump this whenever the layout below changes, including when cards are added to or removed from
+// the registry, since cards are stored by their position in it
+// 2: definitions that aren't in the registry are written out in full
+pub const COMPACT_VERSION: u8 = 2;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompactError {
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    InvalidValue,
+    UnknownCard(u64),
+    TrailingBytes,
+}
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompactError::UnsupportedVersion(version) => {
+                write!(f, "unsupported compact state version {version}")
+            }
+            CompactError::UnexpectedEnd => write!(f, "compact state ends too early"),
+            CompactError::InvalidValue => write!(f, "invalid value in compact state"),
+            CompactError::UnknownCard(position) => write!(f, "unknown card {position}"),
+            CompactError::TrailingBytes => write!(f, "bytes left over after the compact state"),
+        }
+    }
+}
+
+impl std::error::Error for CompactError {}
+
+impl GameState {
+    // a small binary form of the state: numbers are varints and each card is its id and its
+    // position in the card registry, or its whole definition when it isn't one of the registry's,
+    // the rng is left out just as in the serde form
+    pub fn to_compact(&self) -> Vec<u8> {
+        let mut bytes = vec![COMPACT_VERSION];
+        self.encode(&mut bytes);
+        bytes
+    }
+
+    // the rng of the decoded state is freshly seeded, as when deserializing
+    pub fn from_compact(bytes: &[u8]) -> Result<Self, CompactError> {
+        let mut reader = Reader { bytes };
+        let version = reader.byte()?;
+        if version != COMPACT_VERSION {
+            return Err(CompactError::UnsupportedVersion(version));
+        }
+        let game_state = reader.game_state()?;
+        if !reader.bytes.is_empty() {
+            return Err(CompactError::TrailingBytes);
+        }
+        Ok(game_state)
+    }
+
+    // FNV-1a of the compact form without building it, stable across runs and platforms so it
+    // can key a transposition table or name a stored state
+    pub fn compact_hash(&self) -> u64 {
+        let mut hasher = Fnv(FNV_OFFSET_BASIS);
+        hasher.byte(COMPACT_VERSION);
+        self.encode(&mut hasher);
+        hasher.0
+    }
+
+    fn encode(&self, out: &mut impl Encoder) {
+        for player_state in [&self.player1, &self.player2] {
+            out.int(player_state.hull_damage);
+            out.int(player_state.shields);
+            out.int(player_state.short_circuits);
+            out.cards(&player_state.hand);
+            for system in System::ALL {
+                let system_state = player_state.get_system_state_immut(system);
+                out.int(system_state.energy);
+                out.int(system_state.overloads);
+                out.cards(&system_state.hot_wires);
+            }
+        }
+        out.cards(&self.deck);
+        out.cards(&self.discard_pile);
+        out.byte(match self.players_turn {
+            Player::Player1 => 0,
+            Player::Player2 => 1,
+        });
+        out.int(self.actions_left);
+        match &self.turn_state {
+            TurnState::ChoosingAction => out.byte(0),
+            TurnState::ResolvingEffects { effects } => {
+                out.byte(1);
+                out.effects(effects);
+            }
+        }
+    }
+}
+
+trait Encoder {
+    fn byte(&mut self, byte: u8);
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.byte(value as u8);
+    }
+
+    // zigzag, so small negative numbers stay small
+    fn int(&mut self, value: i32) {
+        self.varint(((value << 1) ^ (value >> 31)) as u32 as u64);
+    }
+
+    fn system(&mut self, system: System) {
+        self.byte(System::ALL.iter().position(|&s| s == system).unwrap() as u8);
+    }
+
+    fn cards(&mut self, cards: &[Card]) {
+        self.varint(cards.len() as u64);
+        for card in cards {
+            self.varint(card.id.0 as u64);
+            // 0 is kept for definitions that follow in full
+            match card_definition_position(&card.definition) {
+                Some(position) => self.varint(position as u64 + 1),
+                None => {
+                    self.varint(0);
+                    self.definition(&card.definition);
+                }
+            }
+        }
+    }
+
+    fn definition(&mut self, definition: &CardDefinition) {
+        self.varint(definition.name.len() as u64);
+        for &byte in definition.name.as_bytes() {
+            self.byte(byte);
+        }
+        self.effects(&definition.instant_effects);
+        self.effects(&definition.hot_wire_effects);
+        self.int(definition.hot_wire_cost.short_circuits);
+        self.varint(definition.hot_wire_cost.cards_to_discard as u64);
+        match definition.system {
+            None => self.byte(0),
+            Some(system) => {
+                self.byte(1);
+                self.system(system);
+            }
+        }
+    }
+
+    fn effects(&mut self, effects: &[Effect]) {
+        self.varint(effects.len() as u64);
+        for &effect in effects {
+            self.effect(effect);
+        }
+    }
+
+    fn effect(&mut self, effect: Effect) {
+        let (code, system) = match effect {
+            Effect::GainShortCircuit => (0, None),
+            Effect::LoseShortCircuit => (1, None),
+            Effect::StoreMoreEnergy => (2, None),
+            Effect::UseMoreEnergy => (3, None),
+            Effect::UseLessEnergy => (4, None),
+            Effect::Shield => (5, None),
+            Effect::Attack => (6, None),
+            Effect::DiscardOverload => (7, None),
+            Effect::GainAction => (8, None),
+            Effect::PlayHotWire => (9, None),
+            Effect::Draw => (10, None),
+            Effect::OpponentDiscard => (11, None),
+            Effect::OpponentGainShortCircuit => (12, None),
+            Effect::OpponentLoseShield => (13, None),
+            Effect::OpponentMoveEnergy => (14, None),
+            Effect::OpponentGainOverload => (15, None),
+            Effect::DrawPowerFrom(system) => (16, Some(system)),
+            Effect::MoveEnergy => (17, None),
+            Effect::MoveEnergyTo(system) => (18, Some(system)),
+            Effect::UseSystemCards(system) => (19, Some(system)),
+            Effect::BypassShield => (20, None),
+        };
+        self.byte(code);
+        if let Some(system) = system {
+            self.system(system);
+        }
+    }
+}
+
+impl Encoder for Vec<u8> {
+    fn byte(&mut self, byte: u8) {
+        self.push(byte);
+    }
+}
+
+struct Fnv(u64);
+
+impl Encoder for Fnv {
+    fn byte(&mut self, byte: u8) {
+        self.0 = (self.0 ^ byte as u64).wrapping_mul(FNV_PRIME);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, CompactError> {
+        let (&byte, rest) = self
+            .bytes
+            .split_first()
+            .ok_or(CompactError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, CompactError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(CompactError::InvalidValue)
+    }
+
+    fn int(&mut self) -> Result<i32, CompactError> {
+        let value = u32::try_from(self.varint()?).map_err(|_| CompactError::InvalidValue)?;
+        Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+    }
+
+    // every entry takes at least a byte, so a length can't be more than what is left
+    fn len(&mut self) -> Result<usize, CompactError> {
+        let len = self.varint()?;
+        if len > self.bytes.len() as u64 {
+            return Err(CompactError::UnexpectedEnd);
+        }
+        Ok(len as usize)
+    }
+
+    fn system(&mut self) -> Result<System, CompactError> {
+        System::ALL
+            .get(self.byte()? as usize)
+            .copied()
+            .ok_or(CompactError::InvalidValue)
+    }
+
+    fn cards(&mut self) -> Result<Vec<Card>, CompactError> {
+        let len = self.len()?;
+        (0..len)
+            .map(|_| {
+                let id = u32::try_from(self.varint()?).map_err(|_| CompactError::InvalidValue)?;
+                let definition = match self.varint()? {
+                    0 => Arc::new(self.definition()?),
+                    position => usize::try_from(position - 1)
+                        .ok()
+                        .and_then(card_definition_at)
+                        .map(Arc::clone)
+                        .ok_or(CompactError::UnknownCard(position - 1))?,
+                };
+                Ok(Card {
+                    id: CardId(id),
+                    definition,
+                })
+            })
+            .collect()
+    }
+
+    fn effects(&mut self) -> Result<Vec<Effect>, CompactError> {
+        let len = self.len()?;
+        (0..len).map(|_| self.effect()).collect()
+    }
+
+    fn definition(&mut self) -> Result<CardDefinition, CompactError> {
+        let len = self.len()?;
+        let (name, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| CompactError::InvalidValue)?;
+        let instant_effects = self.effects()?;
+        let hot_wire_effects = self.effects()?;
+        let short_circuits = self.int()?;
+        let cards_to_discard =
+            usize::try_from(self.varint()?).map_err(|_| CompactError::InvalidValue)?;
+        let system = match self.byte()? {
+            0 => None,
+            1 => Some(self.system()?),
+            _ => return Err(CompactError::InvalidValue),
+        };
+        Ok(CardDefinition {
+            instant_effects,
+            hot_wire_effects,
+            hot_wire_cost: HotWireCost {
+                short_circuits,
+                cards_to_discard,
+            },
+            system,
+            name,
+        })
+    }
+
+    fn effect(&mut self) -> Result<Effect, CompactError> {
+        Ok(match self.byte()? {
+            0 => Effect::GainShortCircuit,
+            1 => Effect::LoseShortCircuit,
+            2 => Effect::StoreMoreEnergy,
+            3 => Effect::UseMoreEnergy,
+            4 => Effect::UseLessEnergy,
+            5 => Effect::Shield,
+            6 => Effect::Attack,
+            7 => Effect::DiscardOverload,
+            8 => Effect::GainAction,
+            9 => Effect::PlayHotWire,
+            10 => Effect::Draw,
+            11 => Effect::OpponentDiscard,
+            12 => Effect::OpponentGainShortCircuit,
+            13 => Effect::OpponentLoseShield,
+            14 => Effect::OpponentMoveEnergy,
+            15 => Effect::OpponentGainOverload,
+            16 => Effect::DrawPowerFrom(self.system()?),
+            17 => Effect::MoveEnergy,
+            18 => Effect::MoveEnergyTo(self.system()?),
+            19 => Effect::UseSystemCards(self.system()?),
+            20 => Effect::BypassShield,
+            _ => return Err(CompactError::InvalidValue),
+        })
+    }
+
+    fn system_state(&mut self, system: System) -> Result<SystemState, CompactError> {
+        Ok(SystemState {
+            system,
+            energy: self.int()?,
+            overloads: self.int()?,
+            hot_wires: self.cards()?,
+        })
+    }
+
+    fn player_state(&mut self) -> Result<PlayerState, CompactError> {
+        let hull_damage = self.int()?;
+        let shields = self.int()?;
+        let short_circuits = self.int()?;
+        let hand = self.cards()?;
+        // in the order of System::ALL
+        let fusion_reactor = self.system_state(System::FusionReactor)?;
+        let life_support = self.system_state(System::LifeSupport)?;
+        let weapons_system = self.system_state(System::Weapons)?;
+        let shield_generator = self.system_state(System::ShieldGenerator)?;
+        Ok(PlayerState {
+            hull_damage,
+            shields,
+            short_circuits,
+            hand,
+            fusion_reactor,
+            life_support,
+            shield_generator,
+            weapons_system,
+        })
+    }
+
+    fn game_state(&mut self) -> Result<GameState, CompactError> {
+        let player1 = self.player_state()?;
+        let player2 = self.player_state()?;
+        let deck = self.cards()?;
+        let discard_pile = self.cards()?;
+        let players_turn = match self.byte()? {
+            0 => Player::Player1,
+            1 => Player::Player2,
+            _ => return Err(CompactError::InvalidValue),
+        };
+        let actions_left = self.int()?;
+        let turn_state = match self.byte()? {
+            0 => TurnState::ChoosingAction,
+            1 => TurnState::ResolvingEffects {
+                effects: self.effects()?,
+            },
+            _ => return Err(CompactError::InvalidValue),
+        };
+        Ok(GameState {
+            player1,
+            player2,
+            deck,
+            discard_pile,
+            players_turn,
+            actions_left,
+            turn_state,
+            rng: GameRng::default(),
+        })
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::game::*;
+
+// a plain text picture of the game as one player sees it, the opponent's hand stays hidden
+pub fn render_game_state(game_state: &GameState, me: Player) -> String {
+    let mut out = String::new();
+    let opponent = me.other_player();
+    render_ship(
+        &mut out,
+        "opponent",
+        game_state.my_state_immut(opponent),
+        false,
+    );
+    out += "\n";
+    render_ship(&mut out, "you", game_state.my_state_immut(me), true);
+    out += "\n";
+    writeln!(
+        out,
+        "deck: {} cards, discard pile: {} cards",
+        game_state.deck.len(),
+        game_state.discard_pile.len()
+    )
+    .unwrap();
+    if let Some(winner) = game_state.winner() {
+        let result = if winner == me { "you won" } else { "you lost" };
+        writeln!(out, "game over, {result}").unwrap();
+        return out;
+    }
+    let whose_turn = if game_state.players_turn == me {
+        "your"
+    } else {
+        "opponent's"
+    };
+    writeln!(
+        out,
+        "{whose_turn} turn, {} actions left",
+        game_state.actions_left
+    )
+    .unwrap();
+    if let TurnState::ResolvingEffects { effects } = &game_state.turn_state {
+        writeln!(out, "effects to resolve: {}", list(effects)).unwrap();
+    }
+    out
+}
+
+fn render_ship(out: &mut String, title: &str, state: &PlayerState, show_hand: bool) {
+    writeln!(
+        out,
+        "{title}: hull damage {}/{HULL_DAMAGE_TO_LOSE}, shields {}/{}, short circuits {}",
+        state.hull_damage,
+        state.shields,
+        state.shield_generator.get_allowed_energy(),
+        state.short_circuits
+    )
+    .unwrap();
+    for system in System::ALL {
+        let system_state = state.get_system_state_immut(system);
+        write!(
+            out,
+            "  {:<16} energy {}/{}, uses {}",
+            format!("{system:?}"),
+            system_state.energy,
+            system_state.get_allowed_energy(),
+            system_state.get_energy_used()
+        )
+        .unwrap();
+        if system_state.overloads > 0 {
+            write!(out, ", overloads {}", system_state.overloads).unwrap();
+        }
+        if !system_state.hot_wires.is_empty() {
+            let names: Vec<&str> = system_state
+                .hot_wires
+                .iter()
+                .map(|card| card.name.as_str())
+                .collect();
+            write!(out, ", hot wires {}", names.join(", ")).unwrap();
+        }
+        out.push('\n');
+    }
+    if !show_hand {
+        writeln!(out, "  hand: {} cards", state.hand.len()).unwrap();
+        return;
+    }
+    writeln!(out, "  hand:").unwrap();
+    for (card_index, card) in state.hand.iter().enumerate() {
+        writeln!(out, "    {card_index}) {}", describe_card(card)).unwrap();
+    }
+}
+
+pub fn describe_card(card: &CardDefinition) -> String {
+    let system = card
+        .system
+        .map_or("any system".to_string(), |system| format!("{system:?}"));
+    format!(
+        "{}: instant {} | hot wire on {system} {}, costs {} short circuits and {} discards",
+        card.name,
+        list(&card.instant_effects),
+        list(&card.hot_wire_effects),
+        card.hot_wire_cost.short_circuits,
+        card.hot_wire_cost.cards_to_discard
+    )
+}
+
+// the heading a move is listed under, so that menus don't show hundreds of moves at once
+pub fn menu_group(user_action: &UserAction) -> String {
+    match user_action {
+        UserAction::ChooseAction { action } => match action {
+            Action::PlayInstantCard { .. } => "play a card".to_string(),
+            Action::HotWireCard { .. } => "hot-wire a card".to_string(),
+            Action::ActivateSystem { system, .. } => format!("activate {system:?}"),
+            Action::DiscardOverload { .. } => "discard an overload".to_string(),
+            Action::ReduceShortCircuits => "reduce short circuits".to_string(),
+        },
+        UserAction::ResolveEffect { resolve_effect } => {
+            format!("resolve {:?}", resolve_effect.effect_this_resolves())
+        }
+        UserAction::Pass { .. } => "pass".to_string(),
+        UserAction::StopResolvingEffects => "stop resolving effects".to_string(),
+    }
+}
+
+pub fn describe_user_action(
+    game_state: &GameState,
+    user_action_with_player: &UserActionWithPlayer,
+) -> String {
+    let my_state = game_state.my_state_immut(user_action_with_player.player);
+    let card = |card: &CardRef| match my_state.find_card(*card) {
+        Some(card_index) => my_state.hand[card_index].name.clone(),
+        None => match card {
+            CardRef::Index(card_index) => format!("card {card_index}"),
+            CardRef::Id { id } => format!("card #{}", id.0),
+        },
+    };
+    let discarding = |cards: &[CardRef]| {
+        if cards.is_empty() {
+            String::new()
+        } else {
+            let cards: Vec<String> = cards.iter().map(card).collect();
+            format!(", discarding {}", cards.join(", "))
+        }
+    };
+    match &user_action_with_player.user_action {
+        UserAction::ChooseAction { action } => match action {
+            Action::PlayInstantCard { card: played } => format!("play {}", card(played)),
+            Action::HotWireCard {
+                card: hot_wired,
+                system,
+                discards,
+            } => format!(
+                "hot-wire {} onto {system:?}{}",
+                card(hot_wired),
+                discarding(discards)
+            ),
+            Action::ActivateSystem {
+                system,
+                energy_to_use,
+                energy_distribution,
+            } => {
+                let mut description = format!("activate {system:?}");
+                if let Some(energy_to_use) = energy_to_use {
+                    description += &format!(" using energy from {}", energies(energy_to_use));
+                }
+                if let Some(energy_distribution) = energy_distribution {
+                    description += &format!(" putting energy on {}", energies(energy_distribution));
+                }
+                description
+            }
+            Action::DiscardOverload { system } => format!("discard an overload from {system:?}"),
+            Action::ReduceShortCircuits => "reduce short circuits".to_string(),
+        },
+        UserAction::ResolveEffect { resolve_effect } => match resolve_effect {
+            ResolveEffect::DiscardOverload { system } => {
+                format!("discard an overload from {system:?}")
+            }
+            ResolveEffect::PlayHotWire {
+                card: hot_wired,
+                system,
+                discards,
+            } => format!(
+                "hot-wire {} onto {system:?}{}",
+                card(hot_wired),
+                discarding(discards)
+            ),
+            ResolveEffect::OpponentDiscard { card: discarded } => {
+                format!("discard {}", card(discarded))
+            }
+            ResolveEffect::OpponentGainOverload { system } => {
+                format!("overload the opponent's {system:?}")
+            }
+            ResolveEffect::OpponentMoveEnergy {
+                from_system,
+                to_system,
+            } => format!("move the opponent's energy from {from_system:?} to {to_system:?}"),
+            ResolveEffect::MoveEnergy {
+                from_system,
+                to_system,
+            }
+            | ResolveEffect::MoveEnergyTo {
+                from_system,
+                to_system,
+            } => format!("move energy from {from_system:?} to {to_system:?}"),
+            resolve_effect => format!("{:?}", resolve_effect.effect_this_resolves()),
+        },
+        UserAction::Pass { discards } => format!("pass{}", discarding(discards)),
+        UserAction::StopResolvingEffects => "stop resolving effects".to_string(),
+    }
+}
+
+fn energies(energies: &BTreeMap<System, i32>) -> String {
+    let energies: Vec<String> = energies
+        .iter()
+        .filter(|(_, &energy)| energy > 0)
+        .map(|(system, energy)| format!("{system:?} {energy}"))
+        .collect();
+    if energies.is_empty() {
+        "nothing".to_string()
+    } else {
+        energies.join(", ")
+    }
+}
+
+fn list(effects: &[Effect]) -> String {
+    if effects.is_empty() {
+        return "nothing".to_string();
+    }
+    effects
+        .iter()
+        .map(|effect| format!("{effect:?}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
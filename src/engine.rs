@@ -0,0 +1,292 @@
+// runs games against bots living in other processes, talking line delimited JSON over their
+// stdin and stdout: every line the engine writes is an EngineMessage, and the bot answers each
+// ChooseAction with one line holding either its index in legal_moves or any UserAction the game
+// would accept, within the reply timeout or it forfeits
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::agents::Agent;
+use crate::game::{GameState, Player, UserAction, UserActionWithPlayer};
+
+// how long a bot gets to answer before it forfeits
+pub const DEFAULT_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum EngineMessage {
+    Start {
+        player: Player,
+    },
+    ChooseAction {
+        view: Box<PlayerView>,
+        legal_moves: Vec<UserAction>,
+    },
+    GameOver {
+        winner: Option<Player>,
+    },
+}
+
+// what one player can see: the game with the opponent's hand and the deck left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PlayerView {
+    pub player: Player,
+    pub game_state: GameState,
+    pub opponent_hand_size: usize,
+    pub deck_size: usize,
+}
+
+impl PlayerView {
+    pub fn new(game_state: &GameState, player: Player) -> Self {
+        let mut game_state = game_state.clone();
+        let opponent_hand = match player {
+            Player::Player1 => &mut game_state.player2.hand,
+            Player::Player2 => &mut game_state.player1.hand,
+        };
+        let opponent_hand_size = opponent_hand.len();
+        opponent_hand.clear();
+        let deck_size = game_state.deck.len();
+        game_state.deck.clear();
+        Self {
+            player,
+            game_state,
+            opponent_hand_size,
+            deck_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum BotReply {
+    Index(usize),
+    UserAction(UserAction),
+}
+
+#[derive(Debug)]
+pub enum EngineError {
+    Io(io::Error),
+    BotExited,
+    TimedOut(Duration),
+    MalformedReply(String),
+    IllegalMove(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Io(error) => write!(f, "failed to talk to the bot: {error}"),
+            EngineError::BotExited => write!(f, "the bot exited"),
+            EngineError::TimedOut(timeout) => {
+                write!(f, "the bot didn't reply within {}ms", timeout.as_millis())
+            }
+            EngineError::MalformedReply(line) => write!(f, "malformed reply: {line}"),
+            EngineError::IllegalMove(line) => write!(f, "illegal move: {line}"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<io::Error> for EngineError {
+    fn from(error: io::Error) -> Self {
+        EngineError::Io(error)
+    }
+}
+
+// a bot running as a child process
+pub struct ExternalBot {
+    child: Child,
+    stdin: ChildStdin,
+    // lines from the bot's stdout, read on a thread of their own so that waiting on a bot that
+    // has hung can give up
+    replies: Receiver<io::Result<String>>,
+    reply_timeout: Duration,
+}
+
+impl ExternalBot {
+    // the command is run by the shell, so it can carry arguments, e.g. "python3 bot.py --fast"
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, replies) = mpsc::channel();
+        // ends when the bot closes its stdout, which killing it on drop does too
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                let failed = line.is_err();
+                if sender.send(line).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            replies,
+            reply_timeout: DEFAULT_REPLY_TIMEOUT,
+        })
+    }
+
+    pub fn with_reply_timeout(mut self, reply_timeout: Duration) -> Self {
+        self.reply_timeout = reply_timeout;
+        self
+    }
+
+    fn send(&mut self, engine_message: &EngineMessage) -> Result<(), EngineError> {
+        let line = serde_json::to_string(engine_message).unwrap();
+        writeln!(self.stdin, "{line}").map_err(|error| match error.kind() {
+            io::ErrorKind::BrokenPipe => EngineError::BotExited,
+            _ => EngineError::Io(error),
+        })?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player: Player,
+        legal_moves: &[UserActionWithPlayer],
+    ) -> Result<UserActionWithPlayer, EngineError> {
+        self.send(&EngineMessage::ChooseAction {
+            view: Box::new(PlayerView::new(game_state, player)),
+            legal_moves: legal_moves
+                .iter()
+                .map(|user_action_with_player| user_action_with_player.user_action.clone())
+                .collect(),
+        })?;
+        let line = match self.replies.recv_timeout(self.reply_timeout) {
+            Ok(line) => line?.trim().to_string(),
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(EngineError::TimedOut(self.reply_timeout))
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(EngineError::BotExited),
+        };
+        let user_action_with_player = match serde_json::from_str(&line) {
+            Ok(BotReply::Index(index)) => legal_moves.get(index).cloned(),
+            Ok(BotReply::UserAction(user_action)) => {
+                // bots may still pick cards by their position in the hand
+                let user_action_with_player = game_state.with_card_ids(UserActionWithPlayer {
+                    player,
+                    user_action,
+                });
+                // legal_moves leaves out other spellings of the same move, like discards in
+                // another order, so anything the game accepts counts
+                game_state
+                    .clone()
+                    .receive_user_action(user_action_with_player.clone())
+                    .is_ok()
+                    .then_some(user_action_with_player)
+            }
+            Err(_) => return Err(EngineError::MalformedReply(line)),
+        };
+        user_action_with_player.ok_or(EngineError::IllegalMove(line))
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// either side of a game can be a bot in another process or one of our own agents
+pub enum EngineBot {
+    External(ExternalBot),
+    Agent(Box<dyn Agent>),
+}
+
+impl EngineBot {
+    fn send(&mut self, engine_message: &EngineMessage) -> Result<(), EngineError> {
+        match self {
+            EngineBot::External(bot) => bot.send(engine_message),
+            EngineBot::Agent(_) => Ok(()),
+        }
+    }
+
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player: Player,
+        legal_moves: &[UserActionWithPlayer],
+    ) -> Result<UserActionWithPlayer, EngineError> {
+        match self {
+            EngineBot::External(bot) => bot.choose_action(game_state, player, legal_moves),
+            EngineBot::Agent(agent) => Ok(agent.choose_action(game_state, player, legal_moves)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GameOutcome {
+    Won { winner: Player, turns: u32 },
+    // nobody won within the turn limit
+    Draw { turns: u32 },
+    // the player's bot broke the protocol, which loses it the game
+    Forfeit { player: Player, error: EngineError },
+}
+
+// bots[0] plays Player1 and bots[1] plays Player2
+pub fn run_game(
+    mut game_state: GameState,
+    bots: &mut [EngineBot; 2],
+    max_turns: u32,
+) -> GameOutcome {
+    let forfeit = |player: Player, error: EngineError| GameOutcome::Forfeit { player, error };
+    let mut outcome = None;
+    for (bot, player) in bots.iter_mut().zip([Player::Player1, Player::Player2]) {
+        if let Err(error) = bot.send(&EngineMessage::Start { player }) {
+            outcome = outcome.or(Some(forfeit(player, error)));
+        }
+    }
+    let mut turns = 0;
+    let outcome = outcome.unwrap_or_else(|| loop {
+        if let Some(winner) = game_state.winner() {
+            break GameOutcome::Won { winner, turns };
+        }
+        let player = game_state.next_player_to_act();
+        let legal_moves = game_state.legal_moves(player);
+        if turns >= max_turns || legal_moves.is_empty() {
+            break GameOutcome::Draw { turns };
+        }
+        let bot = match player {
+            Player::Player1 => &mut bots[0],
+            Player::Player2 => &mut bots[1],
+        };
+        let user_action_with_player = match bot.choose_action(&game_state, player, &legal_moves) {
+            Ok(user_action_with_player) => user_action_with_player,
+            Err(error) => break forfeit(player, error),
+        };
+        let players_turn = game_state.players_turn;
+        game_state
+            .receive_user_action(user_action_with_player)
+            .expect("bots only get to make moves the game accepts");
+        if game_state.players_turn != players_turn {
+            turns += 1;
+        }
+    });
+    let winner = match &outcome {
+        GameOutcome::Won { winner, .. } => Some(*winner),
+        GameOutcome::Forfeit { player, .. } => Some(player.other_player()),
+        GameOutcome::Draw { .. } => None,
+    };
+    for bot in bots.iter_mut() {
+        // the game is decided already, a bot that has gone away by now doesn't change that
+        let _ = bot.send(&EngineMessage::GameOver { winner });
+    }
+    outcome
+}
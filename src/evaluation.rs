@@ -0,0 +1,142 @@
+use crate::game::*;
+
+const WIN_SCORE: f64 = 1000.0;
+const HULL_WEIGHT: f64 = 10.0;
+const SHIELD_WEIGHT: f64 = 3.0;
+const SHORT_CIRCUIT_WEIGHT: f64 = -1.5;
+const OVERLOAD_WEIGHT: f64 = -4.0;
+const ACTIVATION_WEIGHT: f64 = 1.5;
+const ENERGY_WEIGHT: f64 = 0.2;
+const HAND_WEIGHT: f64 = 1.0;
+const HOT_WIRE_WEIGHT: f64 = 1.5;
+const ACTION_WEIGHT: f64 = 1.0;
+// effects waiting to be resolved are not guaranteed to happen, so count them for a bit less
+const PENDING_EFFECT_WEIGHT: f64 = 0.8;
+// cards beyond this are discarded when passing
+const MAX_HAND_SIZE: usize = 5;
+
+// how good a state is for one player, split up by what contributes to it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Evaluation {
+    pub win: f64,
+    pub hull: f64,
+    pub shields: f64,
+    pub short_circuits: f64,
+    pub overloads: f64,
+    pub energy: f64,
+    pub hand: f64,
+    pub hot_wires: f64,
+    pub actions: f64,
+    pub pending_effects: f64,
+}
+
+impl Evaluation {
+    pub fn total(&self) -> f64 {
+        self.win
+            + self.hull
+            + self.shields
+            + self.short_circuits
+            + self.overloads
+            + self.energy
+            + self.hand
+            + self.hot_wires
+            + self.actions
+            + self.pending_effects
+    }
+
+    pub fn terms(&self) -> [(&'static str, f64); 10] {
+        [
+            ("win", self.win),
+            ("hull", self.hull),
+            ("shields", self.shields),
+            ("short circuits", self.short_circuits),
+            ("overloads", self.overloads),
+            ("energy", self.energy),
+            ("hand", self.hand),
+            ("hot wires", self.hot_wires),
+            ("actions", self.actions),
+            ("pending effects", self.pending_effects),
+        ]
+    }
+}
+
+pub fn evaluate(game_state: &GameState, player: Player) -> Evaluation {
+    let my_state = game_state.my_state_immut(player);
+    let opponent_state = game_state.my_state_immut(player.other_player());
+    let relative = |value: fn(&PlayerState) -> f64| value(my_state) - value(opponent_state);
+    let sign = |of: Player| if of == player { 1.0 } else { -1.0 };
+    Evaluation {
+        win: game_state
+            .winner()
+            .map_or(0.0, |winner| sign(winner) * WIN_SCORE),
+        hull: -HULL_WEIGHT * relative(|state| state.hull_damage as f64),
+        shields: SHIELD_WEIGHT * relative(|state| state.shields as f64),
+        short_circuits: SHORT_CIRCUIT_WEIGHT * relative(|state| state.short_circuits as f64),
+        overloads: OVERLOAD_WEIGHT * relative(total_overloads),
+        energy: relative(energy_value),
+        hand: HAND_WEIGHT * relative(|state| state.hand.len().min(MAX_HAND_SIZE) as f64),
+        hot_wires: HOT_WIRE_WEIGHT * relative(total_hot_wires),
+        actions: ACTION_WEIGHT * sign(game_state.players_turn) * game_state.actions_left as f64,
+        pending_effects: match &game_state.turn_state {
+            TurnState::ResolvingEffects { effects } => {
+                let active_state = game_state.my_state_immut(game_state.players_turn);
+                PENDING_EFFECT_WEIGHT
+                    * sign(game_state.players_turn)
+                    * effects
+                        .iter()
+                        .map(|&effect| effect_value(effect, active_state))
+                        .sum::<f64>()
+            }
+            TurnState::ChoosingAction => 0.0,
+        },
+    }
+}
+
+fn total_overloads(state: &PlayerState) -> f64 {
+    System::ALL
+        .iter()
+        .map(|&system| state.get_system_state_immut(system).overloads as f64)
+        .sum()
+}
+
+fn total_hot_wires(state: &PlayerState) -> f64 {
+    System::ALL
+        .iter()
+        .map(|&system| state.get_system_state_immut(system).hot_wires.len() as f64)
+        .sum()
+}
+
+// energy is worth most where it pays for activations
+fn energy_value(state: &PlayerState) -> f64 {
+    System::ALL
+        .iter()
+        .map(|&system| {
+            let system_state = state.get_system_state_immut(system);
+            if system == System::FusionReactor {
+                return ENERGY_WEIGHT * system_state.energy as f64;
+            }
+            let activations = system_state.energy / system_state.get_energy_used();
+            ACTIVATION_WEIGHT * activations as f64 + ENERGY_WEIGHT * system_state.energy as f64
+        })
+        .sum()
+}
+
+// roughly what resolving the effect is worth to the player resolving it
+fn effect_value(effect: Effect, state: &PlayerState) -> f64 {
+    match effect {
+        Effect::Attack | Effect::BypassShield => HULL_WEIGHT * 0.6,
+        Effect::Shield if state.shields < state.shield_generator.get_allowed_energy() => {
+            SHIELD_WEIGHT
+        }
+        Effect::GainAction => ACTION_WEIGHT,
+        Effect::Draw | Effect::PlayHotWire => HAND_WEIGHT,
+        Effect::LoseShortCircuit if state.short_circuits > 0 => -SHORT_CIRCUIT_WEIGHT,
+        Effect::GainShortCircuit => SHORT_CIRCUIT_WEIGHT,
+        Effect::OpponentGainShortCircuit => -SHORT_CIRCUIT_WEIGHT,
+        Effect::OpponentLoseShield => SHIELD_WEIGHT,
+        Effect::OpponentGainOverload => -OVERLOAD_WEIGHT,
+        Effect::OpponentDiscard => HAND_WEIGHT,
+        Effect::DiscardOverload if total_overloads(state) > 0.0 => -OVERLOAD_WEIGHT,
+        _ => 0.0,
+    }
+}
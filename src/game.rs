@@ -1,14 +1,22 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::Arc;
 
-use cards::get_deck;
+use cards::get_deck_with_rng;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{RngCore, SeedableRng};
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{Deserialize, Serialize};
 
 use crate::cards;
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Card {
+pub const HULL_DAMAGE_TO_LOSE: i32 = 3;
+
+// what a card does, shared by every copy of it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CardDefinition {
     pub instant_effects: Vec<Effect>,
     pub hot_wire_effects: Vec<Effect>,
     pub hot_wire_cost: HotWireCost,
@@ -16,13 +24,108 @@ pub struct Card {
     pub name: String,
 }
 
+// unique to each card in the game, so copies of the same card can be told apart
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[serde(transparent)]
+pub struct CardId(pub u32);
+
+// how an action picks a card from the hand, either by its id, which keeps pointing at the same
+// card when the hand changes, or by its position, which is what older clients send
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[serde(untagged)]
+pub enum CardRef {
+    Index(usize),
+    Id { id: CardId },
+}
+
+// one card in play, cheap to clone since the definition is shared, and serialized as its id and
+// the name of its definition in the card registry
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "SerializedCard", try_from = "SerializedCard")]
+pub struct Card {
+    pub id: CardId,
+    pub definition: Arc<CardDefinition>,
+}
+
+impl Card {
+    pub fn new(id: CardId, definition: CardDefinition) -> Self {
+        Self {
+            id,
+            definition: Arc::new(definition),
+        }
+    }
+}
+
+impl Deref for Card {
+    type Target = CardDefinition;
+
+    fn deref(&self) -> &CardDefinition {
+        &self.definition
+    }
+}
+
+impl JsonSchema for Card {
+    fn schema_name() -> String {
+        "Card".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SerializedCard::json_schema(gen)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct SerializedCard {
+    id: CardId,
+    name: String,
+}
+
+impl From<Card> for SerializedCard {
+    fn from(card: Card) -> Self {
+        Self {
+            id: card.id,
+            name: card.definition.name.clone(),
+        }
+    }
+}
+
+impl TryFrom<SerializedCard> for Card {
+    type Error = String;
+
+    fn try_from(serialized_card: SerializedCard) -> Result<Self, String> {
+        let definition = cards::card_definition(&serialized_card.name)
+            .ok_or_else(|| format!("unknown card {}", serialized_card.name))?;
+        Ok(Self {
+            id: serialized_card.id,
+            definition: Arc::clone(definition),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct HotWireCost {
     pub short_circuits: i32,
     pub cards_to_discard: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub enum Effect {
     GainShortCircuit,
     LoseShortCircuit,
@@ -50,7 +153,8 @@ pub enum Effect {
     BypassShield,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ResolveEffect {
     GainShortCircuit,
     LoseShortCircuit,
@@ -62,13 +166,16 @@ pub enum ResolveEffect {
     GainAction,
 
     PlayHotWire {
-        card_index: usize,
+        #[serde(alias = "card_index")]
+        card: CardRef,
         system: System,
-        indices_to_discard: Vec<usize>,
+        #[serde(alias = "indices_to_discard")]
+        discards: Vec<CardRef>,
     },
     Draw,
     OpponentDiscard {
-        card_index: usize,
+        #[serde(alias = "card_index")]
+        card: CardRef,
     },
     OpponentGainShortCircuit,
     OpponentLoseShield,
@@ -92,7 +199,7 @@ pub enum ResolveEffect {
 }
 
 impl ResolveEffect {
-    fn effect_this_resolves(&self) -> Effect {
+    pub fn effect_this_resolves(&self) -> Effect {
         match self {
             ResolveEffect::Attack => Effect::Attack,
             ResolveEffect::GainShortCircuit => Effect::GainShortCircuit,
@@ -167,7 +274,7 @@ impl Effect {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct SystemState {
     pub system: System,
     pub energy: i32,
@@ -185,7 +292,7 @@ impl SystemState {
         }
     }
 
-    fn get_hot_wire_effects(&self) -> Vec<Effect> {
+    pub fn get_hot_wire_effects(&self) -> Vec<Effect> {
         let mut system_effects: Vec<Effect> = self.system.starting_effects();
         for hot_wire_card in &self.hot_wires {
             system_effects.append(&mut hot_wire_card.hot_wire_effects.clone());
@@ -200,7 +307,7 @@ impl SystemState {
             .count() as i32
     }
 
-    fn get_energy_used(&self) -> i32 {
+    pub fn get_energy_used(&self) -> i32 {
         self.get_hot_wire_effects()
             .iter()
             .filter_map(|effect| match effect {
@@ -225,7 +332,7 @@ impl SystemState {
         additional
     }
 
-    fn get_allowed_systems_to_draw_energy_from(&self) -> Vec<System> {
+    pub fn get_allowed_systems_to_draw_energy_from(&self) -> Vec<System> {
         let mut additional: Vec<System> = self
             .get_hot_wire_effects()
             .iter()
@@ -239,7 +346,10 @@ impl SystemState {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(
+    PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Serialize, Deserialize, JsonSchema,
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum System {
     FusionReactor,
     LifeSupport,
@@ -248,6 +358,13 @@ pub enum System {
 }
 
 impl System {
+    pub const ALL: [System; 4] = [
+        System::FusionReactor,
+        System::LifeSupport,
+        System::Weapons,
+        System::ShieldGenerator,
+    ];
+
     fn starting_effects(&self) -> Vec<Effect> {
         match self {
             System::FusionReactor => vec![
@@ -285,7 +402,7 @@ impl System {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct PlayerState {
     pub hull_damage: i32,
     pub shields: i32,
@@ -320,6 +437,27 @@ impl PlayerState {
         }
     }
 
+    pub fn get_system_state_immut(&self, system: System) -> &SystemState {
+        match system {
+            System::FusionReactor => &self.fusion_reactor,
+            System::LifeSupport => &self.life_support,
+            System::Weapons => &self.weapons_system,
+            System::ShieldGenerator => &self.shield_generator,
+        }
+    }
+
+    // the card's position in the hand, None if it isn't there
+    pub fn find_card(&self, card: CardRef) -> Option<usize> {
+        match card {
+            CardRef::Index(index) => (index < self.hand.len()).then_some(index),
+            CardRef::Id { id } => self.hand.iter().position(|card| card.id == id),
+        }
+    }
+
+    fn find_cards(&self, cards: &[CardRef]) -> Option<Vec<usize>> {
+        cards.iter().map(|&card| self.find_card(card)).collect()
+    }
+
     fn overload_system(&mut self, system: System) {
         let system_state = self.get_system_state(system);
         system_state.overloads += 1;
@@ -348,15 +486,19 @@ impl Action {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Action {
     HotWireCard {
-        card_index: usize,
+        #[serde(alias = "card_index")]
+        card: CardRef,
         system: System,
-        indices_to_discard: Vec<usize>,
+        #[serde(alias = "indices_to_discard")]
+        discards: Vec<CardRef>,
     },
     PlayInstantCard {
-        card_index: usize,
+        #[serde(alias = "card_index")]
+        card: CardRef,
     },
     ActivateSystem {
         system: System,
@@ -369,21 +511,31 @@ pub enum Action {
     ReduceShortCircuits,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum UserAction {
-    ChooseAction { action: Action },
-    ResolveEffect { resolve_effect: ResolveEffect },
-    Pass { card_indices_to_discard: Vec<usize> },
+    ChooseAction {
+        action: Action,
+    },
+    ResolveEffect {
+        resolve_effect: ResolveEffect,
+    },
+    Pass {
+        #[serde(alias = "card_indices_to_discard")]
+        discards: Vec<CardRef>,
+    },
     StopResolvingEffects,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct UserActionWithPlayer {
     pub player: Player,
     pub user_action: UserAction,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Player {
     Player1,
     Player2,
@@ -398,13 +550,68 @@ impl Player {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+impl FromStr for Player {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "player1" => Ok(Player::Player1),
+            "player2" => Ok(Player::Player2),
+            _ => Err(format!("invalid player {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum TurnState {
     ChoosingAction,
     ResolvingEffects { effects: Vec<Effect> },
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+// the randomness in the game (shuffles and ties), seedable so that games can be replayed
+#[derive(Clone, Debug)]
+pub struct GameRng(StdRng);
+
+impl GameRng {
+    pub fn seed_from_u64(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+// the rng is not part of what players can see, so it never makes two states different
+impl PartialEq for GameRng {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for GameRng {}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct GameState {
     pub player1: PlayerState,
     pub player2: PlayerState,
@@ -413,13 +620,23 @@ pub struct GameState {
     pub players_turn: Player,
     pub actions_left: i32,
     pub turn_state: TurnState,
+    #[serde(skip)]
+    pub rng: GameRng,
 }
 
 impl GameState {
     pub fn start_state() -> Self {
+        Self::start_state_with_rng(GameRng::default())
+    }
+
+    pub fn start_state_with_seed(seed: u64) -> Self {
+        Self::start_state_with_rng(GameRng::seed_from_u64(seed))
+    }
+
+    fn start_state_with_rng(mut rng: GameRng) -> Self {
         let mut player1 = PlayerState::start_state();
         let mut player2 = PlayerState::start_state();
-        let mut deck = get_deck();
+        let mut deck = get_deck_with_rng(&mut rng);
         player1.hand = deck.drain(0..3).collect();
         player2.hand = deck.drain(0..3).collect();
         Self {
@@ -430,11 +647,12 @@ impl GameState {
             deck,
             discard_pile: vec![],
             actions_left: 3,
+            rng,
         }
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum UserActionError {
     NotYourTurn,
     NotEnoughCardsToDiscard,
@@ -464,43 +682,40 @@ pub enum UserActionError {
     CannotDrawPowerFromSystem,
     IncorrectAmountOfEnergyToUse,
     CannotResolveBypassShieldWithoutAttack,
-    MalformedUserActionWithPlayer,
-    SentNonTextMessage,
 }
 
 impl GameState {
     fn hot_wire_card(
         &mut self,
-        card_index: usize,
+        card: CardRef,
         system: System,
-        mut indices_to_discard: Vec<usize>,
+        discards: Vec<CardRef>,
         player: Player,
     ) -> Result<(), UserActionError> {
-        let my_state = self.my_state(player);
-        if card_index >= my_state.hand.len() {
+        let my_state = self.my_state_immut(player);
+        let Some(card_index) = my_state.find_card(card) else {
             return Err(UserActionError::InvalidCardIndex);
-        }
-        let card = my_state.hand.remove(card_index);
-        let system_state = my_state.get_system_state(system);
+        };
+        let card = &my_state.hand[card_index];
         if let Some(card_system) = card.system {
-            let allowed_system_cards = system_state.get_allowed_system_cards();
+            let allowed_system_cards = my_state
+                .get_system_state_immut(system)
+                .get_allowed_system_cards();
             if !allowed_system_cards.contains(&card_system) {
                 return Err(UserActionError::CannotHotWireCardOnThisSystem);
             }
         }
-        system_state.hot_wires.push(card.clone());
-        if system == System::FusionReactor {
-            my_state.fusion_reactor.energy += card
-                .hot_wire_effects
-                .iter()
-                .filter(|&&effect| effect == Effect::StoreMoreEnergy)
-                .count() as i32;
-        }
-        my_state.short_circuits =
-            (my_state.short_circuits + card.hot_wire_cost.short_circuits).max(0);
-        if card.hot_wire_cost.cards_to_discard > my_state.hand.len() {
+        let hand_len = my_state.hand.len() - 1;
+        if card.hot_wire_cost.cards_to_discard > hand_len {
             return Err(UserActionError::NotEnoughCardsToDiscard);
         }
+        if discards.len() != card.hot_wire_cost.cards_to_discard {
+            return Err(UserActionError::WrongNumberOfDiscardIndices);
+        }
+        let Some(mut indices_to_discard) = my_state.find_cards(&discards) else {
+            return Err(UserActionError::InvalidDiscardIndices);
+        };
+        // the discards are made after the hot-wired card has left the hand
         for i in &mut indices_to_discard {
             if *i == card_index {
                 return Err(UserActionError::DiscardingCardPlayed);
@@ -509,10 +724,21 @@ impl GameState {
                 *i -= 1;
             }
         }
-        if indices_to_discard.len() != card.hot_wire_cost.cards_to_discard {
-            return Err(UserActionError::WrongNumberOfDiscardIndices);
+        check_discard_indices(hand_len, &indices_to_discard)?;
+
+        let my_state = self.my_state(player);
+        let card = my_state.hand.remove(card_index);
+        if system == System::FusionReactor {
+            my_state.fusion_reactor.energy += card
+                .hot_wire_effects
+                .iter()
+                .filter(|&&effect| effect == Effect::StoreMoreEnergy)
+                .count() as i32;
         }
-        self.discard(player, indices_to_discard)?;
+        my_state.short_circuits =
+            (my_state.short_circuits + card.hot_wire_cost.short_circuits).max(0);
+        my_state.get_system_state(system).hot_wires.push(card);
+        self.discard(player, indices_to_discard);
         Ok(())
     }
 
@@ -523,19 +749,19 @@ impl GameState {
         let my_state = self.my_state(player);
         let result = match action.clone() {
             Action::HotWireCard {
-                card_index,
+                card,
                 system,
-                indices_to_discard,
-            } => self.hot_wire_card(card_index, system, indices_to_discard, player),
-            Action::PlayInstantCard { card_index } => {
-                if card_index >= my_state.hand.len() {
+                discards,
+            } => self.hot_wire_card(card, system, discards, player),
+            Action::PlayInstantCard { card } => {
+                let Some(card_index) = my_state.find_card(card) else {
                     return Err(UserActionError::InvalidCardIndex);
-                }
+                };
                 let card = my_state.hand.remove(card_index);
-                self.discard_pile.push(card.clone());
                 self.turn_state = TurnState::ResolvingEffects {
-                    effects: card.instant_effects,
+                    effects: card.instant_effects.clone(),
                 };
+                self.discard_pile.push(card);
                 Ok(())
             }
             Action::ActivateSystem {
@@ -548,19 +774,20 @@ impl GameState {
                 }
                 if system == System::FusionReactor {
                     if let Some(energy_distribution) = energy_distribution {
-                        let allocated_energy = energy_distribution.values().sum::<i32>();
-                        if allocated_energy != my_state.fusion_reactor.get_allowed_energy() {
+                        let allocated_energy = total_energy(&energy_distribution);
+                        if allocated_energy != Some(my_state.fusion_reactor.get_allowed_energy()) {
                             return Err(UserActionError::InvalidEnergyDistribution);
                         }
                         if energy_distribution.len() != 4 {
                             return Err(UserActionError::InvalidEnergyDistribution);
                         }
+                        if energy_distribution.iter().any(|(&system, &energy)| {
+                            my_state.get_system_state(system).overloads > 0 && energy > 0
+                        }) {
+                            return Err(UserActionError::CannotPutEnergyOnDisabledSystem);
+                        }
                         for (system, energy) in energy_distribution {
-                            let system_state = my_state.get_system_state(system);
-                            if system_state.overloads > 0 && energy > 0 {
-                                return Err(UserActionError::CannotPutEnergyOnDisabledSystem);
-                            }
-                            system_state.energy = energy;
+                            my_state.get_system_state(system).energy = energy;
                         }
                     } else {
                         return Err(UserActionError::MissingEnergyDistribution);
@@ -572,22 +799,29 @@ impl GameState {
                         default_energy_to_use.insert(system, system_state.get_energy_used());
                         default_energy_to_use
                     });
-                    if energy_to_use.values().sum::<i32>() != system_state.get_energy_used() {
+                    if total_energy(&energy_to_use) != Some(system_state.get_energy_used()) {
                         return Err(UserActionError::IncorrectAmountOfEnergyToUse);
                     }
                     let allowed_systems = system_state.get_allowed_systems_to_draw_energy_from();
+                    // work on a copy of the energies so nothing changes unless it all adds up
+                    let mut energies: BTreeMap<System, i32> = System::ALL
+                        .into_iter()
+                        .map(|system| (system, my_state.get_system_state(system).energy))
+                        .collect();
                     for (system_getting_energy_from, energy_used) in energy_to_use {
                         if !allowed_systems.contains(&system_getting_energy_from) && energy_used > 0
                         {
                             return Err(UserActionError::CannotDrawPowerFromSystem);
                         }
-                        let from_system_state =
-                            my_state.get_system_state(system_getting_energy_from);
-                        if energy_used > from_system_state.energy {
+                        let from_energy = energies.get_mut(&system_getting_energy_from).unwrap();
+                        if energy_used > *from_energy {
                             return Err(UserActionError::NotEnoughEnergyToActivate);
                         }
-                        from_system_state.energy -= energy_used;
-                        my_state.fusion_reactor.energy += energy_used;
+                        *from_energy -= energy_used;
+                        *energies.get_mut(&System::FusionReactor).unwrap() += energy_used;
+                    }
+                    for (system, energy) in energies {
+                        my_state.get_system_state(system).energy = energy;
                     }
                 }
                 self.turn_state = TurnState::ResolvingEffects {
@@ -662,19 +896,20 @@ impl GameState {
                     }
                     ResolveEffect::GainAction => self.actions_left += 1,
                     ResolveEffect::PlayHotWire {
-                        card_index,
+                        card,
                         system,
-                        indices_to_discard,
-                    } => self.hot_wire_card(card_index, system, indices_to_discard, player)?,
+                        discards,
+                    } => self.hot_wire_card(card, system, discards, player)?,
                     ResolveEffect::Draw => {
+                        if self.deck.is_empty() && self.discard_pile.is_empty() {
+                            return Err(UserActionError::NoCardToDraw);
+                        }
                         if self.deck.is_empty() {
                             self.deck.append(&mut self.discard_pile);
-                            self.deck.shuffle(&mut thread_rng());
+                            self.deck.shuffle(&mut self.rng);
                         }
-                        match self.deck.pop() {
-                            Some(card) => self.my_state(player).hand.push(card),
-                            None => return Err(UserActionError::NoCardToDraw),
-                        };
+                        let card = self.deck.pop().unwrap();
+                        self.my_state(player).hand.push(card);
                     }
                     ResolveEffect::OpponentGainShortCircuit => {
                         self.opponent_state(player).short_circuits += 1
@@ -734,20 +969,24 @@ impl GameState {
         player: Player,
     ) -> Result<(), UserActionError> {
         let my_state = self.my_state(player);
-        let from_system_state = my_state.get_system_state(from_system);
-        if from_system_state.energy <= 0 {
+        let from_energy = my_state.get_system_state(from_system).energy;
+        if from_energy <= 0 {
             return Err(UserActionError::NoEnergyToMoveOnSystem);
         }
-        from_system_state.energy -= 1;
-
         let to_system_state = my_state.get_system_state(to_system);
+        let to_energy = if from_system == to_system {
+            from_energy - 1
+        } else {
+            to_system_state.energy
+        };
         if to_system_state.overloads > 0 {
             return Err(UserActionError::CannotPutEnergyOnDisabledSystem);
         }
-        if to_system_state.energy == to_system_state.get_allowed_energy() {
+        if to_energy == to_system_state.get_allowed_energy() {
             return Err(UserActionError::SystemAlreadyHasMaxEnergy);
         }
-        to_system_state.energy += 1;
+        my_state.get_system_state(from_system).energy -= 1;
+        my_state.get_system_state(to_system).energy += 1;
         Ok(())
     }
 
@@ -755,9 +994,20 @@ impl GameState {
         &mut self,
         user_action_with_player: UserActionWithPlayer,
     ) -> Result<(), UserActionError> {
-        let game_state_before = self.clone();
+        let result = self.apply_user_action(user_action_with_player);
+        self.remove_effects_without_immediate_effects();
+        self.remove_opponent_discards_if_no_cards();
+        // TODO: check if someone won
+        result
+    }
+
+    // every check comes before the first change, so a rejected action leaves the state as it was
+    fn apply_user_action(
+        &mut self,
+        user_action_with_player: UserActionWithPlayer,
+    ) -> Result<(), UserActionError> {
         let player = user_action_with_player.player;
-        let result = if self.players_turn == player {
+        if self.players_turn == player {
             match (self.turn_state.clone(), user_action_with_player.user_action) {
                 (TurnState::ChoosingAction, UserAction::ChooseAction { action }) => {
                     self.choose_action(action, player)
@@ -774,24 +1024,26 @@ impl GameState {
                     }
                     Ok(())
                 }
-                (
-                    TurnState::ChoosingAction,
-                    UserAction::Pass {
-                        card_indices_to_discard,
-                    },
-                ) => {
+                (TurnState::ChoosingAction, UserAction::Pass { discards }) => {
                     let my_state = self.my_state(player);
                     if my_state.hand.len() > 5 {
                         let cards_to_discard = my_state.hand.len() - 5;
-                        if cards_to_discard != card_indices_to_discard.len() {
+                        if cards_to_discard != discards.len() {
                             return Err(UserActionError::WrongNumberOfDiscardIndices);
                         }
-                        self.discard(player, card_indices_to_discard)?;
+                        let Some(indices_to_discard) = my_state.find_cards(&discards) else {
+                            return Err(UserActionError::InvalidDiscardIndices);
+                        };
+                        check_discard_indices(my_state.hand.len(), &indices_to_discard)?;
+                        self.discard(player, indices_to_discard);
                     }
                     self.actions_left = 3;
                     self.players_turn = player.other_player();
 
-                    let my_state = self.my_state(player);
+                    let (my_state, rng) = match player {
+                        Player::Player1 => (&mut self.player1, &mut self.rng),
+                        Player::Player2 => (&mut self.player2, &mut self.rng),
+                    };
                     while my_state.short_circuits >= 5 {
                         my_state.short_circuits -= 5;
                         let max_hotwires = [
@@ -816,11 +1068,7 @@ impl GameState {
                         })
                         .cloned()
                         .collect();
-                        my_state.overload_system(
-                            *systems_with_max_overloads
-                                .choose(&mut rand::thread_rng())
-                                .unwrap(),
-                        );
+                        my_state.overload_system(*systems_with_max_overloads.choose(rng).unwrap());
                     }
                     Ok(())
                 }
@@ -850,16 +1098,16 @@ impl GameState {
                     TurnState::ResolvingEffects { mut effects },
                     UserAction::ResolveEffect { resolve_effect },
                 ) => {
-                    if let ResolveEffect::OpponentDiscard { card_index } = resolve_effect {
+                    if let ResolveEffect::OpponentDiscard { card } = resolve_effect {
                         match effects
                             .iter()
                             .position(|&e| e == resolve_effect.effect_this_resolves())
                         {
                             Some(i) => {
                                 let my_state = self.my_state(player);
-                                if card_index >= my_state.hand.len() {
+                                let Some(card_index) = my_state.find_card(card) else {
                                     return Err(UserActionError::InvalidCardIndex);
-                                }
+                                };
                                 let card = my_state.hand.remove(card_index);
                                 self.discard_pile.push(card);
                                 effects.remove(i);
@@ -870,7 +1118,7 @@ impl GameState {
                                 }
                                 Ok(())
                             }
-                            None => return Err(UserActionError::NoMatchingEffectToResolve),
+                            None => Err(UserActionError::NoMatchingEffectToResolve),
                         }
                     } else {
                         Err(UserActionError::NotYourTurn)
@@ -878,32 +1126,17 @@ impl GameState {
                 }
                 _ => Err(UserActionError::NotYourTurn),
             }
-        };
-        if result.is_err() {
-            *self = game_state_before;
         }
-        self.remove_effects_without_immediate_effects();
-        self.remove_opponent_discards_if_no_cards();
-        // TODO: check if someone won
-        result
     }
 
-    fn discard(
-        &mut self,
-        player: Player,
-        mut card_indices: Vec<usize>,
-    ) -> Result<(), UserActionError> {
+    // the indices must have been checked with check_discard_indices
+    fn discard(&mut self, player: Player, mut card_indices: Vec<usize>) {
         card_indices.sort();
         card_indices.reverse();
         for i in card_indices {
-            let my_state = self.my_state(player);
-            if i >= my_state.hand.len() {
-                return Err(UserActionError::InvalidDiscardIndices);
-            }
-            let discarded_card = my_state.hand.remove(i);
+            let discarded_card = self.my_state(player).hand.remove(i);
             self.discard_pile.push(discarded_card);
         }
-        Ok(())
     }
 
     fn remove_effects_without_immediate_effects(&mut self) {
@@ -927,6 +1160,36 @@ impl GameState {
         }
     }
 
+    pub fn winner(&self) -> Option<Player> {
+        if self.player1.hull_damage >= HULL_DAMAGE_TO_LOSE {
+            Some(Player::Player2)
+        } else if self.player2.hull_damage >= HULL_DAMAGE_TO_LOSE {
+            Some(Player::Player1)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_waiting_on(&self, player: Player) -> bool {
+        if self.players_turn == player {
+            return true;
+        }
+        match &self.turn_state {
+            TurnState::ResolvingEffects { effects } => effects.contains(&Effect::OpponentDiscard),
+            TurnState::ChoosingAction => false,
+        }
+    }
+
+    // the opponent has to discard before the active player can finish resolving effects
+    pub fn next_player_to_act(&self) -> Player {
+        let opponent = self.players_turn.other_player();
+        if self.is_waiting_on(opponent) {
+            opponent
+        } else {
+            self.players_turn
+        }
+    }
+
     pub fn my_state_immut(&self, player: Player) -> &PlayerState {
         match player {
             Player::Player1 => &self.player1,
@@ -934,6 +1197,42 @@ impl GameState {
         }
     }
 
+    // the same action picking its cards by id rather than by position, so it can be compared
+    // with the legal moves
+    pub fn with_card_ids(
+        &self,
+        mut user_action_with_player: UserActionWithPlayer,
+    ) -> UserActionWithPlayer {
+        let my_state = self.my_state_immut(user_action_with_player.player);
+        let by_id = |card: &mut CardRef| {
+            if let Some(card_index) = my_state.find_card(*card) {
+                *card = CardRef::Id {
+                    id: my_state.hand[card_index].id,
+                };
+            }
+        };
+        match &mut user_action_with_player.user_action {
+            UserAction::ChooseAction {
+                action: Action::PlayInstantCard { card },
+            }
+            | UserAction::ResolveEffect {
+                resolve_effect: ResolveEffect::OpponentDiscard { card },
+            } => by_id(card),
+            UserAction::ChooseAction {
+                action: Action::HotWireCard { card, discards, .. },
+            }
+            | UserAction::ResolveEffect {
+                resolve_effect: ResolveEffect::PlayHotWire { card, discards, .. },
+            } => {
+                by_id(card);
+                discards.iter_mut().for_each(by_id);
+            }
+            UserAction::Pass { discards } => discards.iter_mut().for_each(by_id),
+            _ => {}
+        }
+        user_action_with_player
+    }
+
     fn opponent_state(&mut self, player: Player) -> &mut PlayerState {
         match player {
             Player::Player1 => &mut self.player2,
@@ -941,3 +1240,25 @@ impl GameState {
         }
     }
 }
+
+// None if any amount is negative or the total doesn't fit, these maps come straight from clients
+fn total_energy(energies: &BTreeMap<System, i32>) -> Option<i32> {
+    energies.values().try_fold(0i32, |total, &energy| {
+        if energy < 0 {
+            None
+        } else {
+            total.checked_add(energy)
+        }
+    })
+}
+
+fn check_discard_indices(hand_len: usize, card_indices: &[usize]) -> Result<(), UserActionError> {
+    let mut seen = BTreeSet::new();
+    if card_indices
+        .iter()
+        .any(|&i| i >= hand_len || !seen.insert(i))
+    {
+        return Err(UserActionError::InvalidDiscardIndices);
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::evaluation::evaluate;
+use crate::game::{GameState, Player, UserActionWithPlayer};
+
+pub const DEFAULT_HINT_COUNT: usize = 5;
+// changes smaller than this are not worth mentioning in a rationale
+const RATIONALE_THRESHOLD: f64 = 0.05;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Hint {
+    pub user_action_with_player: UserActionWithPlayer,
+    // how much the move improves the evaluation of the player's position
+    pub score: f64,
+    // e.g. "shields +3.0, short circuits -1.5"
+    pub rationale: String,
+}
+
+// the player's legal moves, best first, judged by how the position looks right after each one
+pub fn hints(game_state: &GameState, player: Player, count: usize) -> Vec<Hint> {
+    let before = evaluate(game_state, player);
+    let mut hints: Vec<Hint> = game_state
+        .legal_moves_with_outcomes(player)
+        .into_iter()
+        .map(|(user_action_with_player, next_state)| {
+            let after = evaluate(&next_state, player);
+            let mut changes: Vec<(&str, f64)> = after
+                .terms()
+                .into_iter()
+                .zip(before.terms())
+                .map(|((term, after), (_, before))| (term, after - before))
+                .filter(|(_, change)| change.abs() >= RATIONALE_THRESHOLD)
+                .collect();
+            changes.sort_by(|(_, a), (_, b)| b.abs().total_cmp(&a.abs()));
+            let rationale = if changes.is_empty() {
+                "no immediate change".to_string()
+            } else {
+                changes
+                    .iter()
+                    .map(|(term, change)| format!("{term} {change:+.1}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            Hint {
+                user_action_with_player,
+                score: after.total() - before.total(),
+                rationale,
+            }
+        })
+        .collect();
+    // stable, so equally good moves stay in the order they were generated
+    hints.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hints.truncate(count);
+    hints
+}
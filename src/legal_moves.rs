@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+
+use crate::game::*;
+
+impl GameState {
+    // the user actions the player could send right now that the game would accept, pruned of
+    // fusion reactor distributions that put more energy on a system than it can store and of
+    // energy_to_use maps that only repeat the default, the game itself accepts both
+    pub fn legal_moves(&self, player: Player) -> Vec<UserActionWithPlayer> {
+        self.legal_moves_with_outcomes(player)
+            .into_iter()
+            .map(|(user_action_with_player, _)| user_action_with_player)
+            .collect()
+    }
+
+    // each legal move along with the state it leads to
+    pub fn legal_moves_with_outcomes(
+        &self,
+        player: Player,
+    ) -> Vec<(UserActionWithPlayer, GameState)> {
+        if self.winner().is_some() {
+            return vec![];
+        }
+        self.candidate_moves(player)
+            .into_iter()
+            .filter_map(|user_action| {
+                let user_action_with_player = UserActionWithPlayer {
+                    player,
+                    user_action,
+                };
+                let mut next_state = self.clone();
+                next_state
+                    .receive_user_action(user_action_with_player.clone())
+                    .ok()
+                    .map(|()| (user_action_with_player, next_state))
+            })
+            .collect()
+    }
+
+    fn candidate_moves(&self, player: Player) -> Vec<UserAction> {
+        let my_state = self.my_state_immut(player);
+        let hand_size = my_state.hand.len();
+        if self.players_turn != player {
+            return match &self.turn_state {
+                TurnState::ResolvingEffects { effects }
+                    if effects.contains(&Effect::OpponentDiscard) =>
+                {
+                    my_state
+                        .hand
+                        .iter()
+                        .map(|card| UserAction::ResolveEffect {
+                            resolve_effect: ResolveEffect::OpponentDiscard {
+                                card: CardRef::Id { id: card.id },
+                            },
+                        })
+                        .collect()
+                }
+                _ => vec![],
+            };
+        }
+        match &self.turn_state {
+            TurnState::ChoosingAction => {
+                let mut user_actions: Vec<UserAction> =
+                    combinations(hand_size, hand_size.saturating_sub(5))
+                        .into_iter()
+                        .map(|indices| UserAction::Pass {
+                            discards: card_refs(my_state, &indices),
+                        })
+                        .collect();
+                let mut actions = vec![Action::ReduceShortCircuits];
+                for system in System::ALL {
+                    if my_state.get_system_state_immut(system).overloads > 0 {
+                        actions.push(Action::DiscardOverload { system });
+                    }
+                }
+                for card in &my_state.hand {
+                    actions.push(Action::PlayInstantCard {
+                        card: CardRef::Id { id: card.id },
+                    });
+                }
+                for (card, system, discards) in hot_wire_options(my_state) {
+                    actions.push(Action::HotWireCard {
+                        card,
+                        system,
+                        discards,
+                    });
+                }
+                for system in System::ALL {
+                    actions.extend(activate_system_options(my_state, system));
+                }
+                user_actions.extend(
+                    actions
+                        .into_iter()
+                        .map(|action| UserAction::ChooseAction { action }),
+                );
+                user_actions
+            }
+            TurnState::ResolvingEffects { effects } => {
+                let mut user_actions = vec![UserAction::StopResolvingEffects];
+                let mut distinct_effects = effects.clone();
+                distinct_effects.sort();
+                distinct_effects.dedup();
+                for effect in distinct_effects {
+                    user_actions.extend(
+                        effect_resolutions(my_state, effect)
+                            .into_iter()
+                            .map(|resolve_effect| UserAction::ResolveEffect { resolve_effect }),
+                    );
+                }
+                user_actions
+            }
+        }
+    }
+}
+
+fn effect_resolutions(my_state: &PlayerState, effect: Effect) -> Vec<ResolveEffect> {
+    match effect {
+        Effect::GainShortCircuit => vec![ResolveEffect::GainShortCircuit],
+        Effect::LoseShortCircuit => vec![ResolveEffect::LoseShortCircuit],
+        Effect::Shield => vec![ResolveEffect::Shield],
+        Effect::Attack => vec![ResolveEffect::Attack],
+        Effect::DiscardOverload => System::ALL
+            .into_iter()
+            .map(|system| ResolveEffect::DiscardOverload { system })
+            .collect(),
+        Effect::GainAction => vec![ResolveEffect::GainAction],
+        Effect::PlayHotWire => hot_wire_options(my_state)
+            .into_iter()
+            .map(|(card, system, discards)| ResolveEffect::PlayHotWire {
+                card,
+                system,
+                discards,
+            })
+            .collect(),
+        Effect::Draw => vec![ResolveEffect::Draw],
+        Effect::OpponentGainShortCircuit => vec![ResolveEffect::OpponentGainShortCircuit],
+        Effect::OpponentLoseShield => vec![ResolveEffect::OpponentLoseShield],
+        Effect::OpponentMoveEnergy => system_pairs()
+            .map(
+                |(from_system, to_system)| ResolveEffect::OpponentMoveEnergy {
+                    from_system,
+                    to_system,
+                },
+            )
+            .collect(),
+        Effect::OpponentGainOverload => System::ALL
+            .into_iter()
+            .map(|system| ResolveEffect::OpponentGainOverload { system })
+            .collect(),
+        Effect::MoveEnergy => system_pairs()
+            .map(|(from_system, to_system)| ResolveEffect::MoveEnergy {
+                from_system,
+                to_system,
+            })
+            .collect(),
+        Effect::MoveEnergyTo(to_system) => System::ALL
+            .into_iter()
+            .filter(|&from_system| from_system != to_system)
+            .map(|from_system| ResolveEffect::MoveEnergyTo {
+                from_system,
+                to_system,
+            })
+            .collect(),
+        Effect::BypassShield => vec![ResolveEffect::BypassShield],
+        // resolved by the opponent, or has no immediate effect
+        Effect::OpponentDiscard
+        | Effect::StoreMoreEnergy
+        | Effect::UseMoreEnergy
+        | Effect::UseLessEnergy
+        | Effect::UseSystemCards(_)
+        | Effect::DrawPowerFrom(_) => vec![],
+    }
+}
+
+fn system_pairs() -> impl Iterator<Item = (System, System)> {
+    System::ALL.into_iter().flat_map(|from_system| {
+        System::ALL
+            .into_iter()
+            .filter(move |&to_system| to_system != from_system)
+            .map(move |to_system| (from_system, to_system))
+    })
+}
+
+fn hot_wire_options(my_state: &PlayerState) -> Vec<(CardRef, System, Vec<CardRef>)> {
+    let hand_size = my_state.hand.len();
+    let mut options = vec![];
+    for (card_index, card) in my_state.hand.iter().enumerate() {
+        let other_indices: Vec<usize> = (0..hand_size).filter(|&i| i != card_index).collect();
+        for indices in combinations(other_indices.len(), card.hot_wire_cost.cards_to_discard) {
+            let indices_to_discard: Vec<usize> =
+                indices.into_iter().map(|i| other_indices[i]).collect();
+            let discards = card_refs(my_state, &indices_to_discard);
+            for system in System::ALL {
+                options.push((CardRef::Id { id: card.id }, system, discards.clone()));
+            }
+        }
+    }
+    options
+}
+
+// legal moves pick cards by id so they stay the same move however the hand is ordered
+fn card_refs(my_state: &PlayerState, indices: &[usize]) -> Vec<CardRef> {
+    indices
+        .iter()
+        .map(|&i| CardRef::Id {
+            id: my_state.hand[i].id,
+        })
+        .collect()
+}
+
+fn activate_system_options(my_state: &PlayerState, system: System) -> Vec<Action> {
+    let system_state = my_state.get_system_state_immut(system);
+    if system == System::FusionReactor {
+        // never put more energy on a system than it can store
+        let capacities: Vec<i32> = System::ALL
+            .iter()
+            .map(|&system| {
+                let system_state = my_state.get_system_state_immut(system);
+                if system_state.overloads > 0 {
+                    0
+                } else {
+                    system_state.get_allowed_energy()
+                }
+            })
+            .collect();
+        return distributions(system_state.get_allowed_energy(), &capacities)
+            .into_iter()
+            .map(|energies| Action::ActivateSystem {
+                system,
+                energy_to_use: None,
+                energy_distribution: Some(System::ALL.into_iter().zip(energies).collect()),
+            })
+            .collect();
+    }
+    let allowed_systems = system_state.get_allowed_systems_to_draw_energy_from();
+    let mut actions = vec![Action::ActivateSystem {
+        system,
+        energy_to_use: None,
+        energy_distribution: None,
+    }];
+    if allowed_systems.len() > 1 {
+        let mut sources: Vec<System> = allowed_systems;
+        sources.sort();
+        sources.dedup();
+        let capacities: Vec<i32> = sources
+            .iter()
+            .map(|&system| my_state.get_system_state_immut(system).energy)
+            .collect();
+        for energies in distributions(system_state.get_energy_used(), &capacities) {
+            let energy_to_use: BTreeMap<System, i32> = sources
+                .iter()
+                .copied()
+                .zip(energies)
+                .filter(|&(_, energy)| energy > 0)
+                .collect();
+            if energy_to_use.keys().all(|&source| source == system) {
+                // the same as the default energy_to_use above
+                continue;
+            }
+            actions.push(Action::ActivateSystem {
+                system,
+                energy_to_use: Some(energy_to_use),
+                energy_distribution: None,
+            });
+        }
+    }
+    actions
+}
+
+// all ways to split total into one amount per capacity without exceeding any capacity
+fn distributions(total: i32, capacities: &[i32]) -> Vec<Vec<i32>> {
+    match capacities.split_first() {
+        None if total == 0 => vec![vec![]],
+        None => vec![],
+        Some((&capacity, rest)) => (0..=capacity.min(total))
+            .flat_map(|amount| {
+                distributions(total - amount, rest)
+                    .into_iter()
+                    .map(move |mut distribution| {
+                        distribution.insert(0, amount);
+                        distribution
+                    })
+            })
+            .collect(),
+    }
+}
+
+// all ways to choose k of the indices 0..n, in increasing order
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    if k > n {
+        return vec![];
+    }
+    let mut with_last = combinations(n - 1, k - 1);
+    for combination in &mut with_last {
+        combination.push(n - 1);
+    }
+    let mut without_last = combinations(n - 1, k);
+    without_last.append(&mut with_last);
+    without_last
+}
@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate rocket;
+
+pub mod agents;
+pub mod analytics;
+pub mod cards;
+pub mod client;
+pub mod compact;
+pub mod display;
+pub mod engine;
+pub mod evaluation;
+pub mod game;
+pub mod hints;
+mod legal_moves;
+pub mod protocol;
+pub mod server;
+pub mod simulation;
+// tests.rs nests a tests module and marks arms it never expects with assert!(false)
+#[allow(clippy::module_inception, clippy::assertions_on_constants)]
+mod tests;
@@ -0,0 +1,137 @@
+use schemars::{
+    gen::SchemaSettings,
+    schema::{RootSchema, SchemaObject, SubschemaValidation},
+    JsonSchema,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::game::{CardDefinition, GameState, Player, UserActionError, UserActionWithPlayer};
+use crate::hints::Hint;
+
+// bump this whenever a change to the messages below would break existing clients
+// 2: cards in states are sent as their id and the name of their definition, and actions, hints
+// and legal moves pick cards by id under card and discards, which version 1 clients can't parse
+pub const PROTOCOL_VERSION: u32 = 2;
+// the server only speaks the current card formats, so older clients are turned away at the hello
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+pub fn check_protocol_version(protocol_version: u32) -> Result<(), ProtocolError> {
+    if (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol_version) {
+        Ok(())
+    } else {
+        Err(ProtocolError::UnsupportedProtocolVersion {
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            max_protocol_version: PROTOCOL_VERSION,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    // must be the first message sent on a new connection
+    Hello {
+        protocol_version: u32,
+    },
+    Action {
+        request_id: Option<u64>,
+        user_action_with_player: UserActionWithPlayer,
+    },
+    Resync {
+        request_id: Option<u64>,
+    },
+    Ping {
+        request_id: Option<u64>,
+    },
+    // asks for the best moves the player could make right now
+    Hint {
+        request_id: Option<u64>,
+        player: Player,
+        count: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    Welcome {
+        protocol_version: u32,
+        min_protocol_version: u32,
+        max_protocol_version: u32,
+    },
+    State {
+        version: u64,
+        game_state: Box<GameState>,
+    },
+    Patch {
+        version: u64,
+        // RFC 6902 patch from version - 1 to version
+        #[schemars(with = "Vec<serde_json::Value>")]
+        patch: json_patch::Patch,
+    },
+    ActionResult {
+        request_id: Option<u64>,
+        version: u64,
+        result: Result<(), UserActionError>,
+    },
+    Event {
+        event: GameEvent,
+    },
+    Error {
+        request_id: Option<u64>,
+        error: ProtocolError,
+    },
+    Pong {
+        request_id: Option<u64>,
+    },
+    // best first, empty if the game isn't waiting on the player
+    Hints {
+        request_id: Option<u64>,
+        version: u64,
+        hints: Vec<Hint>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum GameEvent {
+    ClientConnected { connections: usize },
+    ClientDisconnected { connections: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ProtocolError {
+    MalformedMessage,
+    SentNonTextMessage,
+    HandshakeRequired,
+    HandshakeAlreadyCompleted,
+    UnsupportedProtocolVersion {
+        min_protocol_version: u32,
+        max_protocol_version: u32,
+    },
+}
+
+// a single schema covering every message that can be sent in either direction
+pub fn protocol_schema() -> RootSchema {
+    let mut generator = SchemaSettings::draft07().into_generator();
+    let client_message = generator.subschema_for::<ClientMessage>();
+    let server_message = generator.subschema_for::<ServerMessage>();
+    // served by /cards, cards in the game state only refer to these by name
+    generator.subschema_for::<CardDefinition>();
+    let mut schema = SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![client_message, server_message]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    schema.metadata().title = Some("ShieldsUpEngineeringProtocol".to_string());
+    RootSchema {
+        meta_schema: generator.settings().meta_schema.clone(),
+        schema,
+        definitions: generator.take_definitions(),
+    }
+}
+
+pub fn protocol_schema_json() -> String {
+    serde_json::to_string_pretty(&protocol_schema()).unwrap() + "\n"
+}
@@ -0,0 +1,535 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::http::ContentType;
+use rocket::http::Status;
+use rocket::tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+use rocket::tokio::{
+    task::{block_in_place, JoinHandle},
+    time::sleep,
+};
+use rocket::{futures::lock::Mutex, get, tokio::select, Build, Rocket, State};
+use ws::{stream::DuplexStream, Message};
+
+use crate::agents::{choose_user_action, Agent, AgentRegistry};
+use crate::cards::card_definitions;
+use crate::game::{CardDefinition, GameState, Player, UserActionError, UserActionWithPlayer};
+use crate::hints::{hints, DEFAULT_HINT_COUNT};
+use crate::protocol::{
+    check_protocol_version, protocol_schema_json, ClientMessage, GameEvent, ProtocolError,
+    ServerMessage, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+
+const GAME_UPDATE_CHANNEL_CAPACITY: usize = 16;
+const DEFAULT_BOT_DELAY_MS: u64 = 1000;
+const DEFAULT_BOT_AGENT: &str = "greedy";
+// in patch mode every version that is a multiple of this is sent as a full snapshot
+const FULL_SNAPSHOT_INTERVAL: u64 = 20;
+
+#[get("/game/<game_name>?<mode>")]
+async fn play_game(
+    ws: ws::WebSocket,
+    game_name: &str,
+    mode: Option<UpdateMode>,
+    games_state: &State<Arc<Mutex<Games>>>,
+) -> ws::Channel<'static> {
+    games_state.lock().await.get_or_create(game_name);
+
+    let games_state = Arc::clone(games_state);
+    let game_name = game_name.to_string();
+    let mut connection = Connection {
+        mode: mode.unwrap_or(UpdateMode::Full),
+        last_sent_version: 0,
+    };
+    ws.channel(move |mut stream| {
+        Box::pin(async move {
+            if !handshake(&mut stream).await? {
+                return stream.close(None).await;
+            }
+            let mut games = games_state.lock().await;
+            let game = games.0.get_mut(&game_name).unwrap();
+            let snapshot = game.snapshot();
+            let mut game_update_receiver = game.game_update_sender.subscribe();
+            game.connections += 1;
+            game.send_event(GameEvent::ClientConnected {
+                connections: game.connections,
+            });
+            drop(games);
+            connection.send_snapshot(&mut stream, snapshot).await;
+            let result = loop {
+                select! {
+                    x = stream.next() => {
+                        match x {
+                            Some(Ok(message)) => handle_message_from_client(message, games_state.clone(), &mut stream, &game_name, &mut connection).await,
+                            Some(Err(e)) => break Err(e),
+                            None => break Ok(()),
+                        }
+                    }
+                    update = game_update_receiver.recv() => {
+                        match update {
+                            Ok(game_update) => connection.send_game_update(&mut stream, &game_update).await,
+                            Err(RecvError::Lagged(_)) => {
+                                // we missed some versions, so resync with the latest state
+                                let snapshot = current_snapshot(&games_state, &game_name).await;
+                                connection.send_snapshot(&mut stream, snapshot).await;
+                            }
+                            Err(RecvError::Closed) => break Ok(()),
+                        }
+                    }
+                }
+            };
+            let mut games = games_state.lock().await;
+            let game = games.0.get_mut(&game_name).unwrap();
+            game.connections -= 1;
+            game.send_event(GameEvent::ClientDisconnected {
+                connections: game.connections,
+            });
+            result
+        })
+    })
+}
+
+// returns whether the client spoke a supported protocol version
+async fn handshake(stream: &mut DuplexStream) -> ws::result::Result<bool> {
+    let Some(message) = stream.next().await else {
+        return Ok(false);
+    };
+    let error = match message? {
+        ws::Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(ClientMessage::Hello { protocol_version }) => {
+                match check_protocol_version(protocol_version) {
+                    Ok(()) => {
+                        let welcome = ServerMessage::Welcome {
+                            protocol_version,
+                            min_protocol_version: MIN_PROTOCOL_VERSION,
+                            max_protocol_version: PROTOCOL_VERSION,
+                        };
+                        send_message(stream, &welcome).await;
+                        return Ok(true);
+                    }
+                    Err(error) => error,
+                }
+            }
+            Ok(_) | Err(_) => ProtocolError::HandshakeRequired,
+        },
+        _ => ProtocolError::HandshakeRequired,
+    };
+    let error = ServerMessage::Error {
+        request_id: None,
+        error,
+    };
+    send_message(stream, &error).await;
+    Ok(false)
+}
+
+async fn handle_message_from_client(
+    message: Message,
+    games_state: Arc<Mutex<Games>>,
+    stream: &mut DuplexStream,
+    game_name: &str,
+    connection: &mut Connection,
+) {
+    let ws::Message::Text(text) = message else {
+        let error = ServerMessage::Error {
+            request_id: None,
+            error: ProtocolError::SentNonTextMessage,
+        };
+        send_message(stream, &error).await;
+        return;
+    };
+    println!("received: {}", text);
+    match serde_json::from_str::<ClientMessage>(&text) {
+        Ok(ClientMessage::Action {
+            request_id,
+            user_action_with_player,
+        }) => {
+            let mut games = games_state.lock().await;
+            let game = games.0.get_mut(game_name).unwrap();
+            let result = game.receive_user_action(user_action_with_player);
+            let action_result = ServerMessage::ActionResult {
+                request_id,
+                version: game.version,
+                result,
+            };
+            drop(games);
+            send_message(stream, &action_result).await;
+        }
+        Ok(ClientMessage::Resync { .. }) => {
+            let snapshot = current_snapshot(&games_state, game_name).await;
+            connection.send_snapshot(stream, snapshot).await;
+        }
+        Ok(ClientMessage::Ping { request_id }) => {
+            send_message(stream, &ServerMessage::Pong { request_id }).await;
+        }
+        Ok(ClientMessage::Hint {
+            request_id,
+            player,
+            count,
+        }) => {
+            let (game_state, version) = {
+                let games = games_state.lock().await;
+                let game = &games.0[game_name];
+                (game.game_state.clone(), game.version)
+            };
+            let hints =
+                block_in_place(|| hints(&game_state, player, count.unwrap_or(DEFAULT_HINT_COUNT)));
+            let hints = ServerMessage::Hints {
+                request_id,
+                version,
+                hints,
+            };
+            send_message(stream, &hints).await;
+        }
+        Ok(ClientMessage::Hello { .. }) => {
+            let error = ServerMessage::Error {
+                request_id: None,
+                error: ProtocolError::HandshakeAlreadyCompleted,
+            };
+            send_message(stream, &error).await;
+        }
+        Err(_) => {
+            let error = ServerMessage::Error {
+                request_id: None,
+                error: ProtocolError::MalformedMessage,
+            };
+            send_message(stream, &error).await;
+        }
+    }
+}
+
+async fn current_snapshot(games_state: &Mutex<Games>, game_name: &str) -> ServerMessage {
+    let games = games_state.lock().await;
+    games.0.get(game_name).unwrap().snapshot()
+}
+
+async fn send_message(stream: &mut DuplexStream, server_message: &ServerMessage) {
+    let _ = stream
+        .send(ws::Message::Text(
+            serde_json::to_string(server_message).unwrap(),
+        ))
+        .await;
+}
+
+#[post("/game/<game_name>/bot?<seat>&<agent>&<delay_ms>")]
+async fn add_bot(
+    game_name: &str,
+    seat: Option<Seat>,
+    agent: Option<&str>,
+    delay_ms: Option<u64>,
+    games_state: &State<Arc<Mutex<Games>>>,
+    agent_registry: &State<AgentRegistry>,
+) -> Status {
+    let Some(agent) = agent_registry.create(agent.unwrap_or(DEFAULT_BOT_AGENT), rand::random())
+    else {
+        return Status::NotFound;
+    };
+    let bot_seat = BotSeat {
+        player: seat.unwrap_or(Seat::Player2).player(),
+        agent,
+        delay: Duration::from_millis(delay_ms.unwrap_or(DEFAULT_BOT_DELAY_MS)),
+    };
+    match seat_bot(games_state, game_name, bot_seat).await {
+        Some(_) => Status::Created,
+        None => Status::Conflict,
+    }
+}
+
+// plays the seat in the background until the game is over, None if a bot has the seat already
+pub(crate) async fn seat_bot(
+    games_state: &Arc<Mutex<Games>>,
+    game_name: &str,
+    bot_seat: BotSeat,
+) -> Option<JoinHandle<()>> {
+    let mut games = games_state.lock().await;
+    let game = games.get_or_create(game_name);
+    if game.bot_seats.contains(&bot_seat.player) {
+        return None;
+    }
+    game.bot_seats.push(bot_seat.player);
+    let game_update_receiver = game.game_update_sender.subscribe();
+    drop(games);
+    Some(rocket::tokio::spawn(drive_bot(
+        Arc::clone(games_state),
+        game_name.to_string(),
+        bot_seat,
+        game_update_receiver,
+    )))
+}
+
+async fn drive_bot(
+    games_state: Arc<Mutex<Games>>,
+    game_name: String,
+    mut bot_seat: BotSeat,
+    mut game_update_receiver: Receiver<Arc<GameUpdate>>,
+) {
+    loop {
+        match bot_seat.take_turn(&games_state, &game_name).await {
+            BotStep::Moved => continue,
+            BotStep::Wait => {}
+            BotStep::Leave => {
+                let mut games = games_state.lock().await;
+                let game = games.0.get_mut(&game_name).unwrap();
+                game.bot_seats.retain(|&player| player != bot_seat.player);
+                return;
+            }
+        }
+        // nothing for the bot to do until the game changes
+        match game_update_receiver.recv().await {
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[get("/")]
+fn test() -> String {
+    "shields up engineering".to_string()
+}
+
+#[get("/agents")]
+fn list_agents(agent_registry: &State<AgentRegistry>) -> (ContentType, String) {
+    let names: Vec<&str> = agent_registry.names().collect();
+    (ContentType::JSON, serde_json::to_string(&names).unwrap())
+}
+
+#[get("/cards")]
+fn list_cards() -> (ContentType, String) {
+    let definitions: Vec<&CardDefinition> = card_definitions()
+        .map(|definition| definition.as_ref())
+        .collect();
+    (
+        ContentType::JSON,
+        serde_json::to_string(&definitions).unwrap(),
+    )
+}
+
+#[get("/schema")]
+fn schema() -> (ContentType, String) {
+    (ContentType::JSON, protocol_schema_json())
+}
+
+#[derive(Default)]
+pub(crate) struct Games(pub(crate) HashMap<String, Game>);
+
+impl Games {
+    fn get_or_create(&mut self, game_name: &str) -> &mut Game {
+        self.0
+            .entry(game_name.to_string())
+            .or_insert_with(|| Game::new(GameState::start_state()))
+    }
+}
+
+pub(crate) struct Game {
+    pub(crate) game_state: GameState,
+    pub(crate) version: u64,
+    game_state_json: serde_json::Value,
+    connections: usize,
+    pub(crate) bot_seats: Vec<Player>,
+    pub(crate) game_update_sender: Sender<Arc<GameUpdate>>,
+}
+
+impl Game {
+    pub(crate) fn new(game_state: GameState) -> Self {
+        let (game_update_sender, _) = broadcast::channel(GAME_UPDATE_CHANNEL_CAPACITY);
+        Self {
+            game_state_json: serde_json::to_value(&game_state).unwrap(),
+            game_state,
+            version: 0,
+            connections: 0,
+            bot_seats: vec![],
+            game_update_sender,
+        }
+    }
+
+    fn snapshot(&self) -> ServerMessage {
+        ServerMessage::State {
+            version: self.version,
+            game_state: Box::new(self.game_state.clone()),
+        }
+    }
+
+    pub(crate) fn receive_user_action(
+        &mut self,
+        user_action_with_player: UserActionWithPlayer,
+    ) -> Result<(), UserActionError> {
+        let result = self.game_state.receive_user_action(user_action_with_player);
+        if result.is_ok() {
+            self.version += 1;
+            let game_state_json = serde_json::to_value(&self.game_state).unwrap();
+            let patch = json_patch::diff(&self.game_state_json, &game_state_json);
+            self.game_state_json = game_state_json;
+            // no receivers just means nobody is connected right now
+            let _ = self
+                .game_update_sender
+                .send(Arc::new(GameUpdate::State(Box::new(StateUpdate {
+                    version: self.version,
+                    game_state: self.game_state.clone(),
+                    patch,
+                }))));
+        }
+        result
+    }
+
+    fn send_event(&self, event: GameEvent) {
+        let _ = self
+            .game_update_sender
+            .send(Arc::new(GameUpdate::Event(event)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromFormField)]
+enum Seat {
+    Player1,
+    Player2,
+}
+
+impl Seat {
+    fn player(&self) -> Player {
+        match self {
+            Seat::Player1 => Player::Player1,
+            Seat::Player2 => Player::Player2,
+        }
+    }
+}
+
+pub(crate) struct BotSeat {
+    pub(crate) player: Player,
+    pub(crate) agent: Box<dyn Agent>,
+    pub(crate) delay: Duration,
+}
+
+enum BotStep {
+    // the bot's move went through and it may be due to move again
+    Moved,
+    // nothing to do until the game changes
+    Wait,
+    // the game is over or the bot can't play it, so the seat is given up
+    Leave,
+}
+
+impl BotSeat {
+    async fn take_turn(&mut self, games_state: &Mutex<Games>, game_name: &str) -> BotStep {
+        let (game_state, version) = {
+            let games = games_state.lock().await;
+            let game = &games.0[game_name];
+            (game.game_state.clone(), game.version)
+        };
+        if game_state.winner().is_some() {
+            return BotStep::Leave;
+        }
+        if !game_state.is_waiting_on(self.player) {
+            return BotStep::Wait;
+        }
+        sleep(self.delay).await;
+        // agents may think for a while, so don't hold the lock or block other tasks meanwhile
+        let Some(user_action_with_player) =
+            block_in_place(|| choose_user_action(self.agent.as_mut(), &game_state, self.player))
+        else {
+            // the game is waiting on the bot, so waiting for it to change would hang the seat
+            error!(
+                "bot for {:?} in {game_name} has no move to make",
+                self.player
+            );
+            return BotStep::Leave;
+        };
+        let mut games = games_state.lock().await;
+        let game = games.0.get_mut(game_name).unwrap();
+        // a changed version means the update is already on its way to the bot
+        if game.version != version {
+            return BotStep::Wait;
+        }
+        match game.receive_user_action(user_action_with_player) {
+            Ok(()) => BotStep::Moved,
+            Err(error) => {
+                // trying the same move again straight away would just spin
+                error!(
+                    "bot for {:?} in {game_name} made a rejected move: {error:?}",
+                    self.player
+                );
+                BotStep::Wait
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromFormField)]
+enum UpdateMode {
+    Full,
+    Patch,
+}
+
+struct Connection {
+    mode: UpdateMode,
+    last_sent_version: u64,
+}
+
+impl Connection {
+    async fn send_snapshot(&mut self, stream: &mut DuplexStream, snapshot: ServerMessage) {
+        if let ServerMessage::State { version, .. } = snapshot {
+            self.last_sent_version = version;
+        }
+        send_message(stream, &snapshot).await;
+    }
+
+    async fn send_game_update(&mut self, stream: &mut DuplexStream, game_update: &GameUpdate) {
+        let state_update = match game_update {
+            GameUpdate::State(state_update) => state_update,
+            GameUpdate::Event(event) => {
+                let event = ServerMessage::Event {
+                    event: event.clone(),
+                };
+                send_message(stream, &event).await;
+                return;
+            }
+        };
+        if state_update.version <= self.last_sent_version {
+            // already covered by a snapshot sent after a resync
+            return;
+        }
+        if self.mode == UpdateMode::Patch
+            && state_update.version == self.last_sent_version + 1
+            && !state_update.version.is_multiple_of(FULL_SNAPSHOT_INTERVAL)
+        {
+            self.last_sent_version = state_update.version;
+            let patch = ServerMessage::Patch {
+                version: state_update.version,
+                patch: state_update.patch.clone(),
+            };
+            send_message(stream, &patch).await;
+        } else {
+            let snapshot = ServerMessage::State {
+                version: state_update.version,
+                game_state: Box::new(state_update.game_state.clone()),
+            };
+            self.send_snapshot(stream, snapshot).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum GameUpdate {
+    State(Box<StateUpdate>),
+    Event(GameEvent),
+}
+
+#[derive(Debug)]
+pub(crate) struct StateUpdate {
+    pub(crate) version: u64,
+    pub(crate) game_state: GameState,
+    // RFC 6902 patch from the previous version to this one
+    pub(crate) patch: json_patch::Patch,
+}
+
+pub fn rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount(
+            "/",
+            routes![play_game, add_bot, list_agents, list_cards, test, schema],
+        )
+        .configure(rocket::Config {
+            address: "0.0.0.0".parse().unwrap(),
+            ..Default::default()
+        })
+        .manage(Arc::new(Mutex::new(Games::default())))
+        .manage(AgentRegistry::default())
+}
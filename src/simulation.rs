@@ -0,0 +1,370 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::agents::{Agent, AgentRegistry};
+use crate::analytics::CardAnalytics;
+use crate::engine::PlayerView;
+use crate::evaluation::evaluate;
+use crate::game::{
+    Action, GameState, Player, ResolveEffect, TurnState, UserAction, UserActionWithPlayer,
+};
+
+// some matchups never finish, e.g. when both sides just keep their shields up
+pub const DEFAULT_MAX_TURNS: u32 = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationConfig {
+    pub agents: [String; 2],
+    pub games: usize,
+    pub seed: u64,
+    pub threads: usize,
+    pub max_turns: u32,
+    // keep every decision in the game records, for exporting training data
+    pub record_decisions: bool,
+}
+
+// how a single game went
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameRecord {
+    // None if the game hit the turn limit
+    pub winner: Option<Player>,
+    pub turns: u32,
+    pub user_actions: u32,
+    pub action_counts: BTreeMap<String, u32>,
+    pub effect_counts: BTreeMap<String, u32>,
+    pub card_plays: Vec<CardPlay>,
+    pub decisions: Vec<Decision>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub view: PlayerView,
+    pub legal_moves: Vec<UserAction>,
+    pub chosen: UserActionWithPlayer,
+}
+
+// one line of the training data export
+#[derive(Debug, Clone, Serialize)]
+pub struct TrainingExample<'a> {
+    pub game: usize,
+    pub step: usize,
+    pub agent: &'a str,
+    pub view: &'a PlayerView,
+    pub legal_moves: &'a [UserAction],
+    pub chosen: &'a UserActionWithPlayer,
+    pub winner: Option<Player>,
+    // from the deciding player's side: 1 for a win, -1 for a loss and 0 for a draw
+    pub outcome: i32,
+}
+
+impl GameRecord {
+    // the game's decisions as JSON lines
+    pub fn training_examples_jsonl(
+        &self,
+        game: usize,
+        agents: &[String; 2],
+        first_agent_player: Player,
+    ) -> String {
+        let mut jsonl = String::new();
+        for (step, decision) in self.decisions.iter().enumerate() {
+            let player = decision.chosen.player;
+            let agent = if player == first_agent_player {
+                &agents[0]
+            } else {
+                &agents[1]
+            };
+            let training_example = TrainingExample {
+                game,
+                step,
+                agent,
+                view: &decision.view,
+                legal_moves: &decision.legal_moves,
+                chosen: &decision.chosen,
+                winner: self.winner,
+                outcome: match self.winner {
+                    Some(winner) if winner == player => 1,
+                    Some(_) => -1,
+                    None => 0,
+                },
+            };
+            jsonl += &serde_json::to_string(&training_example).unwrap();
+            jsonl.push('\n');
+        }
+        jsonl
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardPlay {
+    pub player: Player,
+    pub card: String,
+    pub hot_wire: bool,
+    // how much the player's evaluation changed between playing the card and being done with
+    // its effects, leaving out the bonus for winning
+    pub impact: f64,
+}
+
+impl GameRecord {
+    fn count(&mut self, user_action: &UserAction) {
+        self.user_actions += 1;
+        let (counts, name) = match user_action {
+            UserAction::ChooseAction { action } => (&mut self.action_counts, variant_name(action)),
+            UserAction::Pass { .. } => (&mut self.action_counts, "Pass".to_string()),
+            UserAction::ResolveEffect { resolve_effect } => {
+                (&mut self.effect_counts, variant_name(resolve_effect))
+            }
+            UserAction::StopResolvingEffects => {
+                (&mut self.effect_counts, "StopResolvingEffects".to_string())
+            }
+        };
+        *counts.entry(name).or_default() += 1;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulationSummary {
+    pub games: u32,
+    // indexed like SimulationConfig::agents
+    pub wins: [u32; 2],
+    pub draws: u32,
+    pub player1_wins: u32,
+    pub total_turns: u64,
+    pub total_user_actions: u64,
+    pub action_counts: BTreeMap<String, u64>,
+    pub effect_counts: BTreeMap<String, u64>,
+    pub card_analytics: CardAnalytics,
+}
+
+impl SimulationSummary {
+    // first_agent_player is the seat the first agent played in
+    pub fn add(&mut self, record: &GameRecord, first_agent_player: Player) {
+        self.games += 1;
+        match record.winner {
+            Some(winner) => {
+                let agent = if winner == first_agent_player { 0 } else { 1 };
+                self.wins[agent] += 1;
+                if winner == Player::Player1 {
+                    self.player1_wins += 1;
+                }
+            }
+            None => self.draws += 1,
+        }
+        self.total_turns += record.turns as u64;
+        self.total_user_actions += record.user_actions as u64;
+        for (counts, record_counts) in [
+            (&mut self.action_counts, &record.action_counts),
+            (&mut self.effect_counts, &record.effect_counts),
+        ] {
+            for (name, &count) in record_counts {
+                *counts.entry(name.clone()).or_default() += count as u64;
+            }
+        }
+        self.card_analytics.add(record);
+    }
+
+    pub fn rate(&self, count: u64) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        count as f64 / self.games as f64
+    }
+}
+
+// plays one game to the end, or until max_turns turns have gone by without a winner
+pub fn play_game(
+    mut game_state: GameState,
+    agents: &mut [Box<dyn Agent>; 2],
+    max_turns: u32,
+    record_decisions: bool,
+) -> GameRecord {
+    let mut record = GameRecord::default();
+    // card plays whose effects are still being resolved, with the evaluation before the play
+    let mut open_card_plays = vec![];
+    while record.turns < max_turns {
+        let player = game_state.next_player_to_act();
+        let agent = match player {
+            Player::Player1 => agents[0].as_mut(),
+            Player::Player2 => agents[1].as_mut(),
+        };
+        let legal_moves = game_state.legal_moves(player);
+        if legal_moves.is_empty() {
+            break;
+        }
+        let user_action_with_player = agent.choose_action(&game_state, player, &legal_moves);
+        if record_decisions {
+            record.decisions.push(Decision {
+                view: PlayerView::new(&game_state, player),
+                legal_moves: legal_moves
+                    .into_iter()
+                    .map(|user_action_with_player| user_action_with_player.user_action)
+                    .collect(),
+                chosen: user_action_with_player.clone(),
+            });
+        }
+        let players_turn = game_state.players_turn;
+        let user_action = user_action_with_player.user_action.clone();
+        let card_play = card_played(&game_state, &user_action_with_player);
+        let evaluation_before = evaluation_without_win(&game_state, player);
+        if game_state
+            .receive_user_action(user_action_with_player)
+            .is_err()
+        {
+            // an agent that picks an illegal move would otherwise be asked again forever
+            break;
+        }
+        record.count(&user_action);
+        if let Some(card_play) = card_play {
+            open_card_plays.push((record.card_plays.len(), evaluation_before));
+            record.card_plays.push(card_play);
+        }
+        if game_state.players_turn != players_turn {
+            record.turns += 1;
+        }
+        if game_state.players_turn != players_turn
+            || game_state.turn_state == TurnState::ChoosingAction
+        {
+            close_card_plays(&mut record, &mut open_card_plays, &game_state);
+        }
+    }
+    close_card_plays(&mut record, &mut open_card_plays, &game_state);
+    record.winner = game_state.winner();
+    record
+}
+
+// the card a user action plays from the hand, if any
+fn card_played(
+    game_state: &GameState,
+    user_action_with_player: &UserActionWithPlayer,
+) -> Option<CardPlay> {
+    let (card, hot_wire) = match &user_action_with_player.user_action {
+        UserAction::ChooseAction {
+            action: Action::PlayInstantCard { card },
+        } => (*card, false),
+        UserAction::ChooseAction {
+            action: Action::HotWireCard { card, .. },
+        }
+        | UserAction::ResolveEffect {
+            resolve_effect: ResolveEffect::PlayHotWire { card, .. },
+        } => (*card, true),
+        _ => return None,
+    };
+    let player = user_action_with_player.player;
+    let my_state = game_state.my_state_immut(player);
+    let card = &my_state.hand[my_state.find_card(card)?];
+    Some(CardPlay {
+        player,
+        card: card.name.clone(),
+        hot_wire,
+        impact: 0.0,
+    })
+}
+
+fn close_card_plays(
+    record: &mut GameRecord,
+    open_card_plays: &mut Vec<(usize, f64)>,
+    game_state: &GameState,
+) {
+    for (card_play, evaluation_before) in open_card_plays.drain(..) {
+        let card_play = &mut record.card_plays[card_play];
+        card_play.impact = evaluation_without_win(game_state, card_play.player) - evaluation_before;
+    }
+}
+
+// winning is worth so much more than anything else that it would drown out everything
+fn evaluation_without_win(game_state: &GameState, player: Player) -> f64 {
+    let evaluation = evaluate(game_state, player);
+    evaluation.total() - evaluation.win
+}
+
+pub fn simulate(
+    config: &SimulationConfig,
+    agent_registry: &AgentRegistry,
+) -> Result<SimulationSummary, String> {
+    simulate_with(config, agent_registry, |_, _, _| {})
+}
+
+// each game only depends on the seed and its index, so the thread count doesn't change results,
+// except with agents that think against the clock like the mcts ones, whose moves then depend on
+// how fast the machine is and how busy the other threads keep it
+// on_game sees every game as it finishes, in whatever order they finish
+pub fn simulate_with(
+    config: &SimulationConfig,
+    agent_registry: &AgentRegistry,
+    on_game: impl Fn(usize, &GameRecord, Player) + Sync,
+) -> Result<SimulationSummary, String> {
+    for agent in &config.agents {
+        if agent_registry.create(agent, 0).is_none() {
+            return Err(format!("unknown agent {agent}"));
+        }
+    }
+    let next_game = AtomicUsize::new(0);
+    let records = Mutex::new(vec![]);
+    thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+                if game >= config.games {
+                    break;
+                }
+                let (mut record, first_agent_player) =
+                    play_seeded_game(config, agent_registry, game);
+                on_game(game, &record, first_agent_player);
+                // the summary doesn't need them and they take up a lot of memory
+                record.decisions = vec![];
+                records
+                    .lock()
+                    .unwrap()
+                    .push((game, record, first_agent_player));
+            });
+        }
+    });
+    // add the games up in order, floating point sums depend on it
+    let mut records = records.into_inner().unwrap();
+    records.sort_by_key(|&(game, _, _)| game);
+    let mut summary = SimulationSummary::default();
+    for (_, record, first_agent_player) in &records {
+        summary.add(record, *first_agent_player);
+    }
+    Ok(summary)
+}
+
+fn play_seeded_game(
+    config: &SimulationConfig,
+    agent_registry: &AgentRegistry,
+    game: usize,
+) -> (GameRecord, Player) {
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(game as u64));
+    let mut agents = [
+        agent_registry.create(&config.agents[0], rng.gen()).unwrap(),
+        agent_registry.create(&config.agents[1], rng.gen()).unwrap(),
+    ];
+    // swap seats every game so going first doesn't favour either agent
+    let first_agent_player = if game.is_multiple_of(2) {
+        Player::Player1
+    } else {
+        agents.swap(0, 1);
+        Player::Player2
+    };
+    let game_state = GameState::start_state_with_seed(rng.gen());
+    let record = play_game(
+        game_state,
+        &mut agents,
+        config.max_turns,
+        config.record_decisions,
+    );
+    (record, first_agent_player)
+}
+
+// the name serde gives an enum variant, e.g. "HotWireCard"
+fn variant_name(value: &impl Serialize) -> String {
+    match serde_json::to_value(value).unwrap() {
+        serde_json::Value::String(name) => name,
+        serde_json::Value::Object(map) => map.keys().next().cloned().unwrap_or_default(),
+        other => other.to_string(),
+    }
+}
@@ -1,8 +1,37 @@
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::Arc;
 
-    use crate::{cards::get_deck, client::get_user_action, game::*};
+    use proptest::{prelude::*, sample::Index, test_runner::TestCaseError};
+    use rocket::futures::lock::Mutex;
+    use rocket::futures::{SinkExt, StreamExt};
+    use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use rocket::tokio::{net::TcpStream, time::timeout};
+    use serde::{Deserialize, Serialize};
+    use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream, WebSocketStream};
+
+    use crate::{
+        agents::{
+            choose_user_action, Agent, AgentRegistry, GreedyAgent, MctsAgent, MctsConfig,
+            RandomAgent, Strength,
+        },
+        analytics::card_report_csv,
+        cards::{card_definition, get_deck},
+        client::GameClient,
+        compact::{CompactError, COMPACT_VERSION},
+        display::{describe_user_action, menu_group, render_game_state},
+        engine::{run_game, EngineBot, EngineError, ExternalBot, GameOutcome, PlayerView},
+        evaluation::evaluate,
+        game::*,
+        hints::hints,
+        protocol::{
+            check_protocol_version, protocol_schema_json, ClientMessage, GameEvent, ProtocolError,
+            ServerMessage, PROTOCOL_VERSION,
+        },
+        server::{seat_bot, BotSeat, Game, GameUpdate, Games},
+        simulation::{play_game, simulate, simulate_with, SimulationConfig},
+    };
 
     impl GameState {
         pub fn get_total_cards(&self) -> usize {
@@ -31,8 +60,10 @@ mod tests {
         let mut pass_count = 0;
         let mut stop_resolving_count = 0;
         let deck_len = get_deck().len();
+        let mut agent = RandomAgent::new(0);
         loop {
-            let user_action_with_player = get_user_action(&game_state);
+            let user_action_with_player =
+                choose_user_action(&mut agent, &game_state, game_state.players_turn).unwrap();
             assert_eq!(game_state.get_total_cards(), deck_len);
             let game_state_before = game_state.clone();
             match game_state.receive_user_action(user_action_with_player.clone()) {
@@ -97,7 +128,7 @@ mod tests {
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
             user_action: UserAction::Pass {
-                card_indices_to_discard: vec![5],
+                discards: vec![CardRef::Index(5)],
             },
         });
         assert!(result.is_ok());
@@ -105,6 +136,54 @@ mod tests {
         assert_eq!(game_state.actions_left, 3);
     }
 
+    #[test]
+    fn test_failed_pass_leaves_state_unchanged() {
+        let mut game_state = GameState::start_state();
+        let cards: Vec<Card> = game_state.deck.drain(0..4).collect();
+        game_state.player1.hand.extend(cards);
+        let game_state_before = game_state.clone();
+        let result = game_state.receive_user_action(UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::Pass {
+                discards: vec![CardRef::Index(6), CardRef::Index(6)],
+            },
+        });
+        assert_eq!(result, Err(UserActionError::InvalidDiscardIndices));
+        assert_eq!(game_state, game_state_before);
+
+        // discarding the same card twice would throw away the card after it instead
+        let result = game_state.receive_user_action(UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::Pass {
+                discards: vec![CardRef::Index(3), CardRef::Index(3)],
+            },
+        });
+        assert_eq!(result, Err(UserActionError::InvalidDiscardIndices));
+        assert_eq!(game_state, game_state_before);
+    }
+
+    #[test]
+    fn test_energy_distribution_overflow() {
+        let mut game_state = GameState::start_state();
+        let energy_distribution = BTreeMap::from([
+            (System::FusionReactor, i32::MAX),
+            (System::LifeSupport, i32::MAX),
+            (System::ShieldGenerator, 3),
+            (System::Weapons, 4),
+        ]);
+        let result = game_state.receive_user_action(UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::ChooseAction {
+                action: Action::ActivateSystem {
+                    system: System::FusionReactor,
+                    energy_to_use: None,
+                    energy_distribution: Some(energy_distribution),
+                },
+            },
+        });
+        assert_eq!(result, Err(UserActionError::InvalidEnergyDistribution));
+    }
+
     #[test]
     fn test_activate_fusion_reactor() {
         let mut game_state = GameState::start_state();
@@ -221,25 +300,28 @@ mod tests {
     fn test_shields() {
         let mut game_state = GameState::start_state();
         game_state.player1.hand = vec![
-            Card {
-                instant_effects: vec![],
-                hot_wire_effects: vec![Effect::Shield],
-                hot_wire_cost: HotWireCost {
-                    short_circuits: 7,
-                    cards_to_discard: 1,
+            Card::new(
+                CardId(1),
+                CardDefinition {
+                    instant_effects: vec![],
+                    hot_wire_effects: vec![Effect::Shield],
+                    hot_wire_cost: HotWireCost {
+                        short_circuits: 7,
+                        cards_to_discard: 1,
+                    },
+                    system: Some(System::ShieldGenerator),
+                    ..Default::default()
                 },
-                system: Some(System::ShieldGenerator),
-                ..Default::default()
-            },
+            ),
             Card::default(),
         ];
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
                 action: Action::HotWireCard {
-                    card_index: 0,
+                    card: CardRef::Index(0),
                     system: System::ShieldGenerator,
-                    indices_to_discard: vec![1],
+                    discards: vec![CardRef::Index(1)],
                 },
             },
         });
@@ -298,24 +380,29 @@ mod tests {
     #[test]
     fn test_move_energy() {
         let mut game_state = GameState::start_state();
-        game_state.player1.hand = vec![Card {
-            instant_effects: vec![
-                Effect::MoveEnergy,
-                Effect::MoveEnergyTo(System::ShieldGenerator),
-                Effect::OpponentMoveEnergy,
-            ],
-            hot_wire_effects: vec![],
-            hot_wire_cost: HotWireCost {
-                short_circuits: 0,
-                cards_to_discard: 0,
-            },
-            system: None,
-            ..Default::default()
-        }];
+        game_state.player1.hand = vec![Card::new(
+            CardId(1),
+            CardDefinition {
+                instant_effects: vec![
+                    Effect::MoveEnergy,
+                    Effect::MoveEnergyTo(System::ShieldGenerator),
+                    Effect::OpponentMoveEnergy,
+                ],
+                hot_wire_effects: vec![],
+                hot_wire_cost: HotWireCost {
+                    short_circuits: 0,
+                    cards_to_discard: 0,
+                },
+                system: None,
+                ..Default::default()
+            },
+        )];
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
-                action: Action::PlayInstantCard { card_index: 0 },
+                action: Action::PlayInstantCard {
+                    card: CardRef::Index(0),
+                },
             },
         });
         assert_eq!(result, Ok(()));
@@ -370,35 +457,41 @@ mod tests {
     fn test_use_system_cards() {
         let mut game_state = GameState::start_state();
         game_state.player1.hand = vec![
-            Card {
-                instant_effects: vec![],
-                hot_wire_effects: vec![Effect::UseSystemCards(System::ShieldGenerator)],
-                hot_wire_cost: HotWireCost {
-                    short_circuits: 0,
-                    cards_to_discard: 0,
+            Card::new(
+                CardId(1),
+                CardDefinition {
+                    instant_effects: vec![],
+                    hot_wire_effects: vec![Effect::UseSystemCards(System::ShieldGenerator)],
+                    hot_wire_cost: HotWireCost {
+                        short_circuits: 0,
+                        cards_to_discard: 0,
+                    },
+                    system: Some(System::Weapons),
+                    ..Default::default()
                 },
-                system: Some(System::Weapons),
-                ..Default::default()
-            },
-            Card {
-                instant_effects: vec![],
-                hot_wire_effects: vec![],
-                hot_wire_cost: HotWireCost {
-                    short_circuits: 0,
-                    cards_to_discard: 0,
+            ),
+            Card::new(
+                CardId(2),
+                CardDefinition {
+                    instant_effects: vec![],
+                    hot_wire_effects: vec![],
+                    hot_wire_cost: HotWireCost {
+                        short_circuits: 0,
+                        cards_to_discard: 0,
+                    },
+                    system: Some(System::ShieldGenerator),
+                    ..Default::default()
                 },
-                system: Some(System::ShieldGenerator),
-                ..Default::default()
-            },
+            ),
         ];
 
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
                 action: Action::HotWireCard {
-                    card_index: 0,
+                    card: CardRef::Index(0),
                     system: System::Weapons,
-                    indices_to_discard: vec![],
+                    discards: vec![],
                 },
             },
         });
@@ -409,9 +502,9 @@ mod tests {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
                 action: Action::HotWireCard {
-                    card_index: 0,
+                    card: CardRef::Index(0),
                     system: System::Weapons,
-                    indices_to_discard: vec![],
+                    discards: vec![],
                 },
             },
         });
@@ -422,24 +515,27 @@ mod tests {
     #[test]
     fn test_draw_power_from() {
         let mut game_state = GameState::start_state();
-        game_state.player1.hand = vec![Card {
-            instant_effects: vec![],
-            hot_wire_effects: vec![Effect::DrawPowerFrom(System::LifeSupport)],
-            hot_wire_cost: HotWireCost {
-                short_circuits: 0,
-                cards_to_discard: 0,
-            },
-            system: None,
-            ..Default::default()
-        }];
+        game_state.player1.hand = vec![Card::new(
+            CardId(1),
+            CardDefinition {
+                instant_effects: vec![],
+                hot_wire_effects: vec![Effect::DrawPowerFrom(System::LifeSupport)],
+                hot_wire_cost: HotWireCost {
+                    short_circuits: 0,
+                    cards_to_discard: 0,
+                },
+                system: None,
+                ..Default::default()
+            },
+        )];
 
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
                 action: Action::HotWireCard {
-                    card_index: 0,
+                    card: CardRef::Index(0),
                     system: System::Weapons,
-                    indices_to_discard: vec![],
+                    discards: vec![],
                 },
             },
         });
@@ -471,24 +567,27 @@ mod tests {
     #[test]
     fn test_bypass_shield() {
         let mut game_state = GameState::start_state();
-        game_state.player1.hand = vec![Card {
-            instant_effects: vec![],
-            hot_wire_effects: vec![Effect::BypassShield, Effect::BypassShield],
-            hot_wire_cost: HotWireCost {
-                short_circuits: 0,
-                cards_to_discard: 0,
-            },
-            system: None,
-            ..Default::default()
-        }];
+        game_state.player1.hand = vec![Card::new(
+            CardId(1),
+            CardDefinition {
+                instant_effects: vec![],
+                hot_wire_effects: vec![Effect::BypassShield, Effect::BypassShield],
+                hot_wire_cost: HotWireCost {
+                    short_circuits: 0,
+                    cards_to_discard: 0,
+                },
+                system: None,
+                ..Default::default()
+            },
+        )];
 
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
                 action: Action::HotWireCard {
-                    card_index: 0,
+                    card: CardRef::Index(0),
                     system: System::Weapons,
-                    indices_to_discard: vec![],
+                    discards: vec![],
                 },
             },
         });
@@ -550,9 +649,9 @@ mod tests {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
                 action: Action::HotWireCard {
-                    card_index: 0,
+                    card: CardRef::Index(0),
                     system: System::Weapons,
-                    indices_to_discard: vec![],
+                    discards: vec![],
                 },
             },
         });
@@ -561,9 +660,7 @@ mod tests {
         game_state.player1.short_circuits = 11;
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
-            user_action: UserAction::Pass {
-                card_indices_to_discard: vec![],
-            },
+            user_action: UserAction::Pass { discards: vec![] },
         });
         assert_eq!(result, Ok(()));
         assert_eq!(game_state.player1.short_circuits, 1);
@@ -575,22 +672,1344 @@ mod tests {
     #[test]
     fn test_increase_energy() {
         let mut game_state = GameState::start_state();
-        game_state.player1.hand = vec![Card {
-            hot_wire_effects: vec![Effect::StoreMoreEnergy, Effect::StoreMoreEnergy],
-            ..Card::default()
-        }];
+        game_state.player1.hand = vec![Card::new(
+            CardId(1),
+            CardDefinition {
+                hot_wire_effects: vec![Effect::StoreMoreEnergy, Effect::StoreMoreEnergy],
+                ..CardDefinition::default()
+            },
+        )];
         assert_eq!(game_state.player1.fusion_reactor.energy, 0);
         let result = game_state.receive_user_action(UserActionWithPlayer {
             player: Player::Player1,
             user_action: UserAction::ChooseAction {
                 action: Action::HotWireCard {
-                    card_index: 0,
+                    card: CardRef::Index(0),
                     system: System::FusionReactor,
-                    indices_to_discard: vec![],
+                    discards: vec![],
                 },
             },
         });
         assert_eq!(result, Ok(()));
         assert_eq!(game_state.player1.fusion_reactor.energy, 2);
     }
+
+    #[test]
+    fn test_game_versions() {
+        let mut game = Game::new(GameState::start_state());
+        let mut game_update_receiver = game.game_update_sender.subscribe();
+        assert_eq!(game.version, 0);
+
+        let result = game.receive_user_action(UserActionWithPlayer {
+            player: Player::Player2,
+            user_action: UserAction::StopResolvingEffects,
+        });
+        assert_eq!(result, Err(UserActionError::NotYourTurn));
+        assert_eq!(game.version, 0);
+
+        let result = game.receive_user_action(UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::ChooseAction {
+                action: Action::ReduceShortCircuits,
+            },
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(game.version, 1);
+
+        let result = game.receive_user_action(UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::Pass { discards: vec![] },
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(game.version, 2);
+
+        let GameUpdate::State(first_update) = &*game_update_receiver.try_recv().unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(first_update.version, 1);
+        assert_eq!(first_update.game_state.actions_left, 2);
+        let GameUpdate::State(second_update) = &*game_update_receiver.try_recv().unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(second_update.version, 2);
+        assert_eq!(second_update.game_state, game.game_state);
+        assert!(game_update_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_game_state_patches() {
+        let mut game = Game::new(GameState::start_state());
+        let mut game_update_receiver = game.game_update_sender.subscribe();
+        let mut game_state_json = serde_json::to_value(&game.game_state).unwrap();
+        let mut agent = RandomAgent::new(0);
+        for _ in 0..50 {
+            let player = game.game_state.players_turn;
+            let user_action_with_player =
+                choose_user_action(&mut agent, &game.game_state, player).unwrap();
+            if game.receive_user_action(user_action_with_player).is_ok() {
+                let game_update = game_update_receiver.try_recv().unwrap();
+                let GameUpdate::State(state_update) = &*game_update else {
+                    unreachable!()
+                };
+                json_patch::patch(&mut game_state_json, &state_update.patch).unwrap();
+                assert_eq!(
+                    serde_json::from_value::<GameState>(game_state_json.clone()).unwrap(),
+                    game.game_state
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_protocol_messages() {
+        let client_message = serde_json::from_str::<ClientMessage>(
+            r#"{
+                "type": "Action",
+                "request_id": 7,
+                "user_action_with_player": {
+                    "player": "Player1",
+                    "user_action": "StopResolvingEffects"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            client_message,
+            ClientMessage::Action {
+                request_id: Some(7),
+                ..
+            }
+        ));
+        let client_message = serde_json::from_str::<ClientMessage>(r#"{"type": "Ping"}"#).unwrap();
+        assert!(matches!(
+            client_message,
+            ClientMessage::Ping { request_id: None }
+        ));
+
+        let server_message = ServerMessage::ActionResult {
+            request_id: Some(7),
+            version: 3,
+            result: Err(UserActionError::NotYourTurn),
+        };
+        assert_eq!(
+            serde_json::to_value(&server_message).unwrap(),
+            serde_json::json!({
+                "type": "ActionResult",
+                "request_id": 7,
+                "version": 3,
+                "result": { "Err": "NotYourTurn" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_protocol_version_check() {
+        let client_message =
+            serde_json::from_str::<ClientMessage>(r#"{"type": "Hello", "protocol_version": 2}"#)
+                .unwrap();
+        let ClientMessage::Hello { protocol_version } = client_message else {
+            unreachable!()
+        };
+        assert_eq!(check_protocol_version(protocol_version), Ok(()));
+        assert_eq!(
+            check_protocol_version(1),
+            Err(ProtocolError::UnsupportedProtocolVersion {
+                min_protocol_version: 2,
+                max_protocol_version: 2,
+            })
+        );
+        assert!(check_protocol_version(3).is_err());
+    }
+
+    // run with UPDATE_SCHEMA=1 to regenerate schema/protocol.schema.json after changing the protocol
+    #[test]
+    fn test_protocol_schema_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/protocol.schema.json");
+        let schema = protocol_schema_json();
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(path, &schema).unwrap();
+        }
+        let checked_in_schema = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            checked_in_schema == schema,
+            "{path} is out of date, rerun the tests with UPDATE_SCHEMA=1"
+        );
+    }
+
+    // a recorded game in replays/, final_state is what the actions lead to under the current rules
+    #[derive(Serialize, Deserialize)]
+    struct Replay {
+        seed: u64,
+        actions: Vec<UserActionWithPlayer>,
+        final_state: GameState,
+    }
+
+    fn replay(replay: &Replay) -> Result<GameState, String> {
+        let mut game_state = GameState::start_state_with_seed(replay.seed);
+        for (step, user_action_with_player) in replay.actions.iter().enumerate() {
+            game_state
+                .receive_user_action(user_action_with_player.clone())
+                .map_err(|error| {
+                    format!("step {step} {user_action_with_player:?} failed with {error:?}")
+                })?;
+        }
+        Ok(game_state)
+    }
+
+    // run with UPDATE_REPLAYS=1 to accept the new final states after changing the rules
+    #[test]
+    fn test_replays() {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/replays");
+        let mut paths: Vec<_> = std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "json")
+            })
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "no replays in {directory}");
+        let mut failures = vec![];
+        for path in paths {
+            let mut recorded: Replay =
+                serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let final_state = match replay(&recorded) {
+                Ok(final_state) => final_state,
+                Err(error) => {
+                    // a rule change made a recorded action illegal, the game needs recording again
+                    failures.push(format!("{}: {error}", path.display()));
+                    continue;
+                }
+            };
+            if std::env::var_os("UPDATE_REPLAYS").is_some() {
+                recorded.final_state = final_state.clone();
+                let json = serde_json::to_string_pretty(&recorded).unwrap();
+                std::fs::write(&path, json + "\n").unwrap();
+            }
+            let diff = json_patch::diff(
+                &serde_json::to_value(&recorded.final_state).unwrap(),
+                &serde_json::to_value(&final_state).unwrap(),
+            );
+            if !diff.is_empty() {
+                failures.push(format!(
+                    "{}: final state differs, rerun the tests with UPDATE_REPLAYS=1 if the rules \
+                     changed on purpose\n{}",
+                    path.display(),
+                    serde_json::to_string_pretty(&diff).unwrap()
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut game_state = GameState::start_state();
+        assert!(game_state.legal_moves(Player::Player2).is_empty());
+        let legal_moves = game_state.legal_moves(Player::Player1);
+        assert!(legal_moves.contains(&UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::Pass { discards: vec![] },
+        }));
+        for user_action_with_player in &legal_moves {
+            assert_eq!(
+                game_state
+                    .clone()
+                    .receive_user_action(user_action_with_player.clone()),
+                Ok(())
+            );
+        }
+
+        game_state.turn_state = TurnState::ResolvingEffects {
+            effects: vec![Effect::OpponentDiscard],
+        };
+        assert!(game_state.is_waiting_on(Player::Player2));
+        let legal_moves = game_state.legal_moves(Player::Player2);
+        assert_eq!(legal_moves.len(), 3);
+        let mut agent = AgentRegistry::default().create("random", 0).unwrap();
+        let user_action_with_player =
+            choose_user_action(agent.as_mut(), &game_state, Player::Player2).unwrap();
+        assert_eq!(user_action_with_player.player, Player::Player2);
+        assert_eq!(
+            game_state.receive_user_action(user_action_with_player),
+            Ok(())
+        );
+        assert_eq!(game_state.player2.hand.len(), 2);
+        assert!(!game_state.is_waiting_on(Player::Player2));
+
+        game_state.player1.hull_damage = HULL_DAMAGE_TO_LOSE;
+        assert_eq!(game_state.winner(), Some(Player::Player2));
+        assert!(game_state.legal_moves(Player::Player1).is_empty());
+    }
+
+    #[test]
+    fn test_evaluation_is_zero_sum() {
+        let mut game_state = GameState::start_state();
+        game_state.player2.hull_damage = 1;
+        game_state.player1.short_circuits = 2;
+        game_state.turn_state = TurnState::ResolvingEffects {
+            effects: vec![Effect::Attack, Effect::Draw],
+        };
+        let player1_evaluation = evaluate(&game_state, Player::Player1);
+        let player2_evaluation = evaluate(&game_state, Player::Player2);
+        assert_eq!(player1_evaluation.total(), -player2_evaluation.total());
+        assert!(player1_evaluation.hull > 0.0);
+        assert!(player1_evaluation.short_circuits < 0.0);
+        assert!(player1_evaluation.pending_effects > 0.0);
+
+        game_state.player2.hull_damage = HULL_DAMAGE_TO_LOSE;
+        assert!(evaluate(&game_state, Player::Player1).win > 0.0);
+    }
+
+    #[test]
+    fn test_greedy_agent_attacks() {
+        let mut game_state = GameState::start_state();
+        let mut agent = GreedyAgent::new(0);
+        let user_action_with_player = UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::ChooseAction {
+                action: Action::ActivateSystem {
+                    system: System::Weapons,
+                    energy_to_use: None,
+                    energy_distribution: None,
+                },
+            },
+        };
+        assert_eq!(
+            game_state.receive_user_action(user_action_with_player),
+            Ok(())
+        );
+        let user_action_with_player =
+            choose_user_action(&mut agent, &game_state, Player::Player1).unwrap();
+        assert_eq!(
+            user_action_with_player.user_action,
+            UserAction::ResolveEffect {
+                resolve_effect: ResolveEffect::Attack
+            }
+        );
+    }
+
+    #[test]
+    fn test_mcts_agent() {
+        let mut game_state = GameState::start_state_with_seed(0);
+        // only the iterations bound the search, so the seed fixes every choice
+        let config = MctsConfig {
+            iterations: 30,
+            time_limit: std::time::Duration::MAX,
+            ..MctsConfig::with_strength(Strength::Easy)
+        };
+        let mut agent = MctsAgent::new(config, 0);
+        for _ in 0..5 {
+            let player = game_state.next_player_to_act();
+            let legal_moves = game_state.legal_moves(player);
+            let user_action_with_player =
+                choose_user_action(&mut agent, &game_state, player).unwrap();
+            assert!(legal_moves.contains(&user_action_with_player));
+            assert_eq!(
+                game_state.receive_user_action(user_action_with_player),
+                Ok(())
+            );
+        }
+
+        // the attack wins the game on the spot, which drawing first only puts off
+        let mut game_state = GameState::start_state_with_seed(0);
+        game_state.player1.hand.clear();
+        game_state.player2.shields = 0;
+        game_state.player2.hull_damage = HULL_DAMAGE_TO_LOSE - 1;
+        game_state.turn_state = TurnState::ResolvingEffects {
+            effects: vec![Effect::Attack, Effect::Draw],
+        };
+        let user_action_with_player =
+            choose_user_action(&mut agent, &game_state, Player::Player1).unwrap();
+        assert_eq!(
+            user_action_with_player.user_action,
+            UserAction::ResolveEffect {
+                resolve_effect: ResolveEffect::Attack
+            }
+        );
+    }
+
+    #[test]
+    fn test_simulation_is_reproducible() {
+        assert_eq!(
+            GameState::start_state_with_seed(7),
+            GameState::start_state_with_seed(7)
+        );
+
+        let mut config = SimulationConfig {
+            agents: ["random".to_string(), "greedy".to_string()],
+            games: 6,
+            seed: 7,
+            threads: 1,
+            max_turns: 100,
+            record_decisions: false,
+        };
+        let agent_registry = AgentRegistry::default();
+        let summary = simulate(&config, &agent_registry).unwrap();
+        assert_eq!(summary.games, 6);
+        assert_eq!(summary.wins[0] + summary.wins[1] + summary.draws, 6);
+        assert!(summary.wins[1] > summary.wins[0]);
+        let user_actions: u64 = summary
+            .action_counts
+            .values()
+            .chain(summary.effect_counts.values())
+            .sum();
+        assert_eq!(user_actions, summary.total_user_actions);
+
+        config.threads = 3;
+        assert_eq!(simulate(&config, &agent_registry).unwrap(), summary);
+
+        config.agents[0] = "nobody".to_string();
+        assert!(simulate(&config, &agent_registry).is_err());
+    }
+
+    #[test]
+    fn test_card_analytics() {
+        let mut agents: [Box<dyn Agent>; 2] =
+            [Box::new(GreedyAgent::new(1)), Box::new(RandomAgent::new(2))];
+        let record = play_game(GameState::start_state_with_seed(3), &mut agents, 100, false);
+        let card_plays = record.action_counts.get("PlayInstantCard").unwrap_or(&0)
+            + record.action_counts.get("HotWireCard").unwrap_or(&0)
+            + record.effect_counts.get("PlayHotWire").unwrap_or(&0);
+        assert!(card_plays > 0);
+        assert_eq!(record.card_plays.len(), card_plays as usize);
+
+        let mut config = SimulationConfig {
+            agents: ["greedy".to_string(), "random".to_string()],
+            games: 4,
+            seed: 3,
+            threads: 2,
+            max_turns: 100,
+            record_decisions: false,
+        };
+        let summary = simulate(&config, &AgentRegistry::default()).unwrap();
+        let reports = summary.card_analytics.report();
+        let deck = get_deck();
+        assert_eq!(
+            reports.iter().map(|report| report.copies).sum::<usize>(),
+            deck.len()
+        );
+        let plays: u64 = reports
+            .iter()
+            .map(|report| report.instant_plays + report.hot_wire_plays)
+            .sum();
+        assert_eq!(
+            plays,
+            summary.action_counts["PlayInstantCard"]
+                + summary.action_counts.get("HotWireCard").unwrap_or(&0)
+                + summary.effect_counts.get("PlayHotWire").unwrap_or(&0)
+        );
+        assert_eq!(card_report_csv(&reports).lines().count(), reports.len() + 1);
+
+        config.threads = 1;
+        let summary_in_one_thread = simulate(&config, &AgentRegistry::default()).unwrap();
+        assert_eq!(summary_in_one_thread.card_analytics, summary.card_analytics);
+    }
+
+    #[test]
+    fn test_hints() {
+        let mut game_state = GameState::start_state();
+        game_state.turn_state = TurnState::ResolvingEffects {
+            effects: vec![Effect::Attack, Effect::Shield],
+        };
+        game_state.player1.shields = 1;
+        assert_eq!(hints(&game_state, Player::Player2, 5), vec![]);
+
+        let best_moves = hints(&game_state, Player::Player1, 5);
+        assert_eq!(best_moves.len(), 3);
+        assert_eq!(
+            best_moves[0].user_action_with_player.user_action,
+            UserAction::ResolveEffect {
+                resolve_effect: ResolveEffect::Shield
+            }
+        );
+        assert!(best_moves[0].rationale.starts_with("shields +3.0"));
+        assert!(best_moves[0].score >= best_moves[1].score);
+        assert!(best_moves[1].score >= best_moves[2].score);
+        assert_eq!(
+            best_moves[2].user_action_with_player.user_action,
+            UserAction::StopResolvingEffects
+        );
+
+        let hint_request: ClientMessage = serde_json::from_str(
+            r#"{"type": "Hint", "request_id": 3, "player": "Player1", "count": null}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            hint_request,
+            ClientMessage::Hint {
+                request_id: Some(3),
+                player: Player::Player1,
+                count: None
+            }
+        ));
+    }
+
+    // serves the app on a free local port and returns its websocket url
+    async fn launch_test_server() -> String {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let rocket = crate::server::rocket();
+        let figment = rocket
+            .figment()
+            .clone()
+            .merge(("address", "127.0.0.1"))
+            .merge(("port", port))
+            .merge(("log_level", "off"));
+        let rocket = rocket.configure(figment).ignite().await.unwrap();
+        rocket::tokio::spawn(rocket.launch());
+        while rocket::tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_err()
+        {
+            rocket::tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        format!("ws://127.0.0.1:{port}")
+    }
+
+    #[rocket::async_test]
+    async fn test_game_client() {
+        let server_url = launch_test_server().await;
+        let mut client = GameClient::connect(&server_url, "client").await.unwrap();
+        assert_eq!(client.version(), 0);
+
+        let result = client
+            .act(UserActionWithPlayer {
+                player: Player::Player2,
+                user_action: UserAction::StopResolvingEffects,
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, Err(UserActionError::NotYourTurn));
+
+        let result = client
+            .act(UserActionWithPlayer {
+                player: Player::Player1,
+                user_action: UserAction::ChooseAction {
+                    action: Action::ReduceShortCircuits,
+                },
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, Ok(()));
+        assert_eq!(client.version(), 1);
+        assert_eq!(client.game_state().actions_left, 2);
+
+        let hints = client.hints(Player::Player1, 3).await.unwrap();
+        assert_eq!(hints.len(), 3);
+
+        let other_client = GameClient::connect(&server_url, "client").await.unwrap();
+        assert_eq!(other_client.version(), 1);
+        assert_eq!(other_client.game_state(), client.game_state());
+    }
+
+    type TestSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    // a raw connection to a game, so the tests see exactly what the server sends
+    async fn open_socket(server_url: &str, game_name: &str) -> TestSocket {
+        let (socket, _) = connect_async(format!("{server_url}/game/{game_name}"))
+            .await
+            .unwrap();
+        socket
+    }
+
+    async fn send_to_server(socket: &mut TestSocket, client_message: &ClientMessage) {
+        let text = serde_json::to_string(client_message).unwrap();
+        socket.send(tungstenite::Message::Text(text)).await.unwrap();
+    }
+
+    // None once the server has closed the connection
+    async fn receive_from_server(socket: &mut TestSocket) -> Option<ServerMessage> {
+        loop {
+            let message = timeout(std::time::Duration::from_secs(5), socket.next())
+                .await
+                .expect("timed out waiting for the server");
+            match message {
+                Some(Ok(tungstenite::Message::Text(text))) => {
+                    return Some(serde_json::from_str(&text).unwrap())
+                }
+                Some(Ok(tungstenite::Message::Close(_))) | None => return None,
+                Some(Ok(_)) => {}
+                Some(Err(error)) => panic!("websocket error: {error}"),
+            }
+        }
+    }
+
+    // says hello and returns the version of the state the server pushes first
+    async fn join_game(socket: &mut TestSocket) -> u64 {
+        let hello = ClientMessage::Hello {
+            protocol_version: PROTOCOL_VERSION,
+        };
+        send_to_server(socket, &hello).await;
+        assert!(matches!(
+            receive_from_server(socket).await,
+            Some(ServerMessage::Welcome { .. })
+        ));
+        match receive_from_server(socket).await {
+            Some(ServerMessage::State { version, .. }) => version,
+            message => panic!("expected the initial state, got {message:?}"),
+        }
+    }
+
+    async fn expect_event(socket: &mut TestSocket, expected_event: GameEvent) {
+        match receive_from_server(socket).await {
+            Some(ServerMessage::Event { event }) => assert_eq!(event, expected_event),
+            message => panic!("expected {expected_event:?}, got {message:?}"),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_server_broadcasts_to_every_connection() {
+        let server_url = launch_test_server().await;
+        let mut first = open_socket(&server_url, "broadcast").await;
+        assert_eq!(join_game(&mut first).await, 0);
+        expect_event(&mut first, GameEvent::ClientConnected { connections: 1 }).await;
+        let mut second = open_socket(&server_url, "broadcast").await;
+        assert_eq!(join_game(&mut second).await, 0);
+        expect_event(&mut second, GameEvent::ClientConnected { connections: 2 }).await;
+        expect_event(&mut first, GameEvent::ClientConnected { connections: 2 }).await;
+
+        let pass = ClientMessage::Action {
+            request_id: Some(1),
+            user_action_with_player: UserActionWithPlayer {
+                player: Player::Player1,
+                user_action: UserAction::Pass { discards: vec![] },
+            },
+        };
+        send_to_server(&mut first, &pass).await;
+        match receive_from_server(&mut first).await {
+            Some(ServerMessage::ActionResult {
+                request_id,
+                version,
+                result,
+            }) => {
+                assert_eq!(request_id, Some(1));
+                assert_eq!(version, 1);
+                assert_eq!(result, Ok(()));
+            }
+            message => panic!("expected an action result, got {message:?}"),
+        }
+        for socket in [&mut first, &mut second] {
+            match receive_from_server(socket).await {
+                Some(ServerMessage::State {
+                    version,
+                    game_state,
+                }) => {
+                    assert_eq!(version, 1);
+                    assert_eq!(game_state.players_turn, Player::Player2);
+                }
+                message => panic!("expected the new state, got {message:?}"),
+            }
+        }
+
+        // a rejected action is only answered to the sender and isn't broadcast
+        send_to_server(&mut first, &pass).await;
+        match receive_from_server(&mut first).await {
+            Some(ServerMessage::ActionResult {
+                version, result, ..
+            }) => {
+                assert_eq!(version, 1);
+                assert_eq!(result, Err(UserActionError::NotYourTurn));
+            }
+            message => panic!("expected an action result, got {message:?}"),
+        }
+
+        first.close(None).await.unwrap();
+        expect_event(
+            &mut second,
+            GameEvent::ClientDisconnected { connections: 1 },
+        )
+        .await;
+
+        // a late joiner starts from the current state
+        let mut third = open_socket(&server_url, "broadcast").await;
+        assert_eq!(join_game(&mut third).await, 1);
+    }
+
+    #[rocket::async_test]
+    async fn test_server_rejects_bad_messages() {
+        let server_url = launch_test_server().await;
+        let mut socket = open_socket(&server_url, "bad-messages").await;
+        join_game(&mut socket).await;
+        expect_event(&mut socket, GameEvent::ClientConnected { connections: 1 }).await;
+
+        let mut expect_error = async |message: tungstenite::Message, expected_error| {
+            socket.send(message).await.unwrap();
+            match receive_from_server(&mut socket).await {
+                Some(ServerMessage::Error { error, .. }) => assert_eq!(error, expected_error),
+                message => panic!("expected {expected_error:?}, got {message:?}"),
+            }
+        };
+        expect_error(
+            tungstenite::Message::Text("not json".to_string()),
+            ProtocolError::MalformedMessage,
+        )
+        .await;
+        expect_error(
+            tungstenite::Message::Text(r#"{"type": "Action"}"#.to_string()),
+            ProtocolError::MalformedMessage,
+        )
+        .await;
+        expect_error(
+            tungstenite::Message::Binary(vec![1, 2, 3]),
+            ProtocolError::SentNonTextMessage,
+        )
+        .await;
+        let hello = serde_json::to_string(&ClientMessage::Hello {
+            protocol_version: PROTOCOL_VERSION,
+        })
+        .unwrap();
+        expect_error(
+            tungstenite::Message::Text(hello),
+            ProtocolError::HandshakeAlreadyCompleted,
+        )
+        .await;
+        // the connection is still usable after all that
+        send_to_server(
+            &mut socket,
+            &ClientMessage::Ping {
+                request_id: Some(7),
+            },
+        )
+        .await;
+        assert!(matches!(
+            receive_from_server(&mut socket).await,
+            Some(ServerMessage::Pong {
+                request_id: Some(7)
+            })
+        ));
+
+        // skipping the handshake gets an error and the connection closed
+        let mut socket = open_socket(&server_url, "bad-messages").await;
+        send_to_server(&mut socket, &ClientMessage::Ping { request_id: None }).await;
+        assert!(matches!(
+            receive_from_server(&mut socket).await,
+            Some(ServerMessage::Error {
+                error: ProtocolError::HandshakeRequired,
+                ..
+            })
+        ));
+        assert!(receive_from_server(&mut socket).await.is_none());
+    }
+
+    #[rocket::async_test]
+    async fn test_server_rejects_version_1_clients() {
+        // version 1 clients expect full cards and hand indices, which the server no longer sends
+        let server_url = launch_test_server().await;
+        let mut socket = open_socket(&server_url, "version-1").await;
+        send_to_server(
+            &mut socket,
+            &ClientMessage::Hello {
+                protocol_version: 1,
+            },
+        )
+        .await;
+        match receive_from_server(&mut socket).await {
+            Some(ServerMessage::Error { error, .. }) => assert_eq!(
+                error,
+                ProtocolError::UnsupportedProtocolVersion {
+                    min_protocol_version: 2,
+                    max_protocol_version: PROTOCOL_VERSION,
+                }
+            ),
+            message => panic!("expected the version to be rejected, got {message:?}"),
+        }
+        assert!(receive_from_server(&mut socket).await.is_none());
+    }
+
+    // the status code of an empty POST, rocket's local client can't share the launched server
+    async fn post_to_server(server_url: &str, path: &str) -> u16 {
+        let address = server_url.trim_start_matches("ws://");
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "POST {path} HTTP/1.1\r\nHost: {address}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.split(' ').nth(1).unwrap().parse().unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn test_server_bot_seats() {
+        let server_url = launch_test_server().await;
+        let mut socket = open_socket(&server_url, "bot-seat").await;
+        assert_eq!(join_game(&mut socket).await, 0);
+        expect_event(&mut socket, GameEvent::ClientConnected { connections: 1 }).await;
+
+        let path = "/game/bot-seat/bot?seat=Player1&agent=random&delay_ms=0";
+        assert_eq!(post_to_server(&server_url, path).await, 201);
+        // player 1 goes first, so the bot's moves show up on the socket
+        match receive_from_server(&mut socket).await {
+            Some(ServerMessage::State { version, .. }) => assert_eq!(version, 1),
+            message => panic!("expected the bot's move, got {message:?}"),
+        }
+        assert_eq!(post_to_server(&server_url, path).await, 409);
+        let path = "/game/bot-seat/bot?seat=Player2&agent=nope";
+        assert_eq!(post_to_server(&server_url, path).await, 404);
+    }
+
+    #[rocket::async_test]
+    async fn test_bot_leaves_seat() {
+        let mut game_over = GameState::start_state_with_seed(0);
+        game_over.player2.hull_damage = HULL_DAMAGE_TO_LOSE;
+        // waiting on player 2 to discard from an empty hand, so there is no move to make
+        let mut stuck = GameState::start_state_with_seed(0);
+        stuck.player2.hand.clear();
+        stuck.turn_state = TurnState::ResolvingEffects {
+            effects: vec![Effect::OpponentDiscard],
+        };
+        assert!(stuck.is_waiting_on(Player::Player2));
+        for (game_name, game_state, player) in [
+            ("over", game_over, Player::Player1),
+            ("stuck", stuck, Player::Player2),
+        ] {
+            let games_state = Arc::new(Mutex::new(Games::default()));
+            games_state
+                .lock()
+                .await
+                .0
+                .insert(game_name.to_string(), Game::new(game_state));
+            let bot_seat = BotSeat {
+                player,
+                agent: Box::new(RandomAgent::new(0)),
+                delay: std::time::Duration::ZERO,
+            };
+            let bot = seat_bot(&games_state, game_name, bot_seat).await.unwrap();
+            timeout(std::time::Duration::from_secs(5), bot)
+                .await
+                .expect("the bot should give up the seat")
+                .unwrap();
+            assert_eq!(games_state.lock().await.0[game_name].bot_seats, vec![]);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_card_registry() {
+        let deck = get_deck();
+        let ids: BTreeSet<CardId> = deck.iter().map(|card| card.id).collect();
+        assert_eq!(ids.len(), deck.len());
+
+        // cards go over the wire as their id and name and come back with the full definition
+        let card = &deck[0];
+        let json = serde_json::to_value(card).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"id": card.id.0, "name": card.name})
+        );
+        assert_eq!(&serde_json::from_value::<Card>(json).unwrap(), card);
+        assert!(
+            serde_json::from_value::<Card>(serde_json::json!({"id": 0, "name": "nope"})).is_err()
+        );
+
+        let client = rocket::local::asynchronous::Client::tracked(crate::server::rocket())
+            .await
+            .unwrap();
+        let response = client.get("/cards").dispatch().await;
+        let definitions: Vec<CardDefinition> =
+            serde_json::from_str(&response.into_string().await.unwrap()).unwrap();
+        for card in &deck {
+            assert!(definitions.contains(&card.definition));
+        }
+    }
+
+    #[test]
+    fn test_card_refs() {
+        let game_state = GameState::start_state_with_seed(0);
+        let card = game_state.player1.hand[2].clone();
+        let play = |card| UserActionWithPlayer {
+            player: Player::Player1,
+            user_action: UserAction::ChooseAction {
+                action: Action::PlayInstantCard { card },
+            },
+        };
+
+        // older clients still pick cards by their position in the hand
+        let by_index: UserActionWithPlayer = serde_json::from_value(serde_json::json!({
+            "player": "Player1",
+            "user_action": {"ChooseAction": {"action": {"PlayInstantCard": {"card_index": 2}}}}
+        }))
+        .unwrap();
+        assert_eq!(by_index, play(CardRef::Index(2)));
+        let by_id = game_state.with_card_ids(by_index.clone());
+        assert_eq!(by_id, play(CardRef::Id { id: card.id }));
+        assert_eq!(
+            serde_json::to_value(&by_id).unwrap()["user_action"]["ChooseAction"]["action"],
+            serde_json::json!({"PlayInstantCard": {"card": {"id": card.id.0}}})
+        );
+        assert!(game_state.legal_moves(Player::Player1).contains(&by_id));
+        let mut next_state = game_state.clone();
+        assert_eq!(next_state.receive_user_action(by_index.clone()), Ok(()));
+        assert_eq!(next_state.discard_pile.last(), Some(&card));
+
+        // after the hand changes the id still finds the card while the index doesn't
+        let mut game_state = game_state;
+        game_state.player1.hand.remove(0);
+        let mut next_state = game_state.clone();
+        assert_eq!(
+            next_state.receive_user_action(by_index),
+            Err(UserActionError::InvalidCardIndex)
+        );
+        assert_eq!(next_state, game_state);
+        assert_eq!(next_state.receive_user_action(by_id), Ok(()));
+        assert_eq!(next_state.discard_pile.last(), Some(&card));
+
+        let missing = CardRef::Id {
+            id: game_state.deck[0].id,
+        };
+        assert_eq!(
+            game_state.clone().receive_user_action(play(missing)),
+            Err(UserActionError::InvalidCardIndex)
+        );
+    }
+
+    #[test]
+    fn test_compact_state() {
+        let mut game_state = GameState::start_state_with_seed(3);
+        let mut agents = [GreedyAgent::new(3), GreedyAgent::new(4)];
+        let mut hashes = BTreeSet::new();
+        for _ in 0..40 {
+            let compact = game_state.to_compact();
+            let decoded = GameState::from_compact(&compact).unwrap();
+            assert_eq!(decoded, game_state);
+            let json = serde_json::to_string(&game_state).unwrap();
+            assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+            assert!(compact.len() * 10 < json.len());
+            assert_eq!(decoded.compact_hash(), game_state.compact_hash());
+            hashes.insert(game_state.compact_hash());
+
+            let player = game_state.next_player_to_act();
+            let legal_moves = game_state.legal_moves(player);
+            let agent = match player {
+                Player::Player1 => &mut agents[0],
+                Player::Player2 => &mut agents[1],
+            };
+            let user_action_with_player = agent.choose_action(&game_state, player, &legal_moves);
+            game_state
+                .receive_user_action(user_action_with_player)
+                .unwrap();
+        }
+        assert_eq!(hashes.len(), 40);
+
+        // every kind of effect, including those naming a system
+        let mut effects = vec![Effect::GainShortCircuit, Effect::BypassShield];
+        for system in System::ALL {
+            effects.push(Effect::DrawPowerFrom(system));
+            effects.push(Effect::MoveEnergyTo(system));
+            effects.push(Effect::UseSystemCards(system));
+        }
+        game_state.turn_state = TurnState::ResolvingEffects { effects };
+        let compact = game_state.to_compact();
+        assert_eq!(GameState::from_compact(&compact), Ok(game_state.clone()));
+
+        assert_eq!(
+            GameState::from_compact(&compact[..compact.len() - 1]),
+            Err(CompactError::UnexpectedEnd)
+        );
+        assert_eq!(
+            GameState::from_compact(&[compact.as_slice(), &[0]].concat()),
+            Err(CompactError::TrailingBytes)
+        );
+        let mut other_version = compact.clone();
+        other_version[0] = COMPACT_VERSION + 1;
+        assert_eq!(
+            GameState::from_compact(&other_version),
+            Err(CompactError::UnsupportedVersion(COMPACT_VERSION + 1))
+        );
+
+        // a copy of a registry definition is stored just like the shared one
+        let attack = card_definition("attack_01").unwrap();
+        let mut copied = game_state.clone();
+        copied.player1.hand = vec![Card::new(CardId(90), (**attack).clone())];
+        let mut shared = game_state.clone();
+        shared.player1.hand = vec![Card {
+            id: CardId(90),
+            definition: Arc::clone(attack),
+        }];
+        assert_eq!(copied.to_compact(), shared.to_compact());
+        assert_eq!(copied.compact_hash(), shared.compact_hash());
+
+        // cards from outside the registry, even one borrowing a registry name, come back as they were
+        let mut custom = game_state.clone();
+        custom.player1.hand = vec![
+            Card::default(),
+            Card::new(
+                CardId(91),
+                CardDefinition {
+                    hot_wire_effects: vec![Effect::MoveEnergyTo(System::Weapons)],
+                    system: Some(System::LifeSupport),
+                    ..(**attack).clone()
+                },
+            ),
+        ];
+        let compact = custom.to_compact();
+        assert_eq!(GameState::from_compact(&compact), Ok(custom.clone()));
+        assert_ne!(custom.compact_hash(), shared.compact_hash());
+    }
+
+    #[test]
+    fn test_display() {
+        let game_state = GameState::start_state_with_seed(0);
+        let rendered = render_game_state(&game_state, Player::Player1);
+        for card in &game_state.player1.hand {
+            assert!(rendered.contains(&card.name));
+        }
+        assert!(rendered.contains("hand: 3 cards"));
+        assert!(rendered.contains("your turn, 3 actions left"));
+
+        // moves in the same menu have to be told apart by their descriptions
+        let mut menu = BTreeSet::new();
+        for user_action_with_player in game_state.legal_moves(Player::Player1) {
+            let description = describe_user_action(&game_state, &user_action_with_player);
+            let group = menu_group(&user_action_with_player.user_action);
+            assert!(menu.insert((group, description)));
+        }
+        let card = &game_state.player1.hand[0].name;
+        assert!(menu.contains(&("play a card".to_string(), format!("play {card}"))));
+    }
+
+    #[test]
+    fn test_external_bots() {
+        let game_state = GameState::start_state_with_seed(5);
+        let view = PlayerView::new(&game_state, Player::Player2);
+        assert_eq!(view.game_state.player1.hand, vec![]);
+        assert_eq!(view.game_state.player2.hand, game_state.player2.hand);
+        assert_eq!(view.opponent_hand_size, 3);
+        assert_eq!(view.deck_size, game_state.deck.len());
+
+        // always picks the first legal move
+        let first_move_bot = r#"while read line; do
+            case "$line" in *ChooseAction*) echo 0;; esac
+        done"#;
+        let mut bots = [
+            EngineBot::External(ExternalBot::spawn(first_move_bot).unwrap()),
+            EngineBot::Agent(Box::new(RandomAgent::new(5))),
+        ];
+        let outcome = run_game(game_state.clone(), &mut bots, 30);
+        assert!(matches!(
+            outcome,
+            GameOutcome::Won { .. } | GameOutcome::Draw { .. }
+        ));
+
+        // the game takes discards in any order, not just the one legal_moves lists them in
+        let mut full_hand = game_state.clone();
+        let drawn = full_hand.deck.split_off(full_hand.deck.len() - 4);
+        full_hand.player1.hand.extend(drawn);
+        let hand = &full_hand.player1.hand;
+        let pass = UserAction::Pass {
+            discards: vec![CardRef::Id { id: hand[6].id }, CardRef::Id { id: hand[2].id }],
+        };
+        let legal_moves = full_hand.legal_moves(Player::Player1);
+        assert!(!legal_moves.iter().any(|m| m.user_action == pass));
+        let pass_bot = format!(
+            "while read line; do echo '{}'; done",
+            serde_json::to_string(&pass).unwrap()
+        );
+        let mut bots = [
+            EngineBot::External(ExternalBot::spawn(&pass_bot).unwrap()),
+            EngineBot::Agent(Box::new(RandomAgent::new(5))),
+        ];
+        let outcome = run_game(full_hand, &mut bots, 1);
+        assert!(matches!(outcome, GameOutcome::Draw { turns: 1 }));
+
+        let mut bots = [
+            EngineBot::Agent(Box::new(RandomAgent::new(5))),
+            EngineBot::External(ExternalBot::spawn("while read line; do echo 1000; done").unwrap()),
+        ];
+        let outcome = run_game(game_state.clone(), &mut bots, 30);
+        assert!(matches!(
+            outcome,
+            GameOutcome::Forfeit {
+                player: Player::Player2,
+                error: EngineError::IllegalMove(_)
+            }
+        ));
+
+        // a bot that hangs forfeits rather than stalling the game
+        let hung_bot = ExternalBot::spawn("exec sleep 60")
+            .unwrap()
+            .with_reply_timeout(std::time::Duration::from_millis(100));
+        let mut bots = [
+            EngineBot::Agent(Box::new(RandomAgent::new(5))),
+            EngineBot::External(hung_bot),
+        ];
+        let outcome = run_game(game_state, &mut bots, 30);
+        assert!(matches!(
+            outcome,
+            GameOutcome::Forfeit {
+                player: Player::Player2,
+                error: EngineError::TimedOut(_)
+            }
+        ));
+    }
+
+    #[test]
+    fn test_training_export() {
+        let config = SimulationConfig {
+            agents: ["greedy".to_string(), "random".to_string()],
+            games: 2,
+            seed: 11,
+            threads: 2,
+            max_turns: 100,
+            record_decisions: true,
+        };
+        let jsonl = std::sync::Mutex::new(String::new());
+        let summary = simulate_with(
+            &config,
+            &AgentRegistry::default(),
+            |game, record, first_agent_player| {
+                assert_eq!(record.decisions.len(), record.user_actions as usize);
+                *jsonl.lock().unwrap() +=
+                    &record.training_examples_jsonl(game, &config.agents, first_agent_player);
+            },
+        )
+        .unwrap();
+        let jsonl = jsonl.into_inner().unwrap();
+        assert_eq!(jsonl.lines().count() as u64, summary.total_user_actions);
+        for line in jsonl.lines() {
+            let example: serde_json::Value = serde_json::from_str(line).unwrap();
+            let chosen: UserActionWithPlayer =
+                serde_json::from_value(example["chosen"].clone()).unwrap();
+            let legal_moves: Vec<UserAction> =
+                serde_json::from_value(example["legal_moves"].clone()).unwrap();
+            assert!(legal_moves.contains(&chosen.user_action));
+            let view: GameState =
+                serde_json::from_value(example["view"]["game_state"].clone()).unwrap();
+            assert_eq!(view.deck, vec![]);
+            let outcome = match serde_json::from_value::<Option<Player>>(example["winner"].clone())
+                .unwrap()
+            {
+                Some(winner) if winner == chosen.player => 1,
+                Some(_) => -1,
+                None => 0,
+            };
+            assert_eq!(example["outcome"], outcome);
+        }
+    }
+
+    fn system_strategy() -> impl Strategy<Value = System> {
+        prop::sample::select(System::ALL.to_vec())
+    }
+
+    // sometimes every system, since a fusion reactor distribution has to name all four
+    fn energies_strategy() -> impl Strategy<Value = BTreeMap<System, i32>> {
+        prop_oneof![
+            prop::collection::btree_map(system_strategy(), -2..6, 0..=4),
+            prop::array::uniform4(-1..4i32)
+                .prop_map(|energies| System::ALL.into_iter().zip(energies).collect()),
+        ]
+    }
+
+    // ids only go up to the size of the deck, so most of them are somewhere in the game
+    fn card_ref_strategy() -> impl Strategy<Value = CardRef> {
+        prop_oneof![
+            (0..8usize).prop_map(CardRef::Index),
+            (0..32u32).prop_map(|id| CardRef::Id { id: CardId(id) }),
+        ]
+    }
+
+    fn card_refs_strategy() -> impl Strategy<Value = Vec<CardRef>> {
+        prop::collection::vec(card_ref_strategy(), 0..3)
+    }
+
+    fn resolve_effect_strategy() -> impl Strategy<Value = ResolveEffect> {
+        prop_oneof![
+            Just(ResolveEffect::GainShortCircuit),
+            Just(ResolveEffect::LoseShortCircuit),
+            Just(ResolveEffect::Shield),
+            Just(ResolveEffect::Attack),
+            system_strategy().prop_map(|system| ResolveEffect::DiscardOverload { system }),
+            Just(ResolveEffect::GainAction),
+            (card_ref_strategy(), system_strategy(), card_refs_strategy()).prop_map(
+                |(card, system, discards)| ResolveEffect::PlayHotWire {
+                    card,
+                    system,
+                    discards,
+                }
+            ),
+            Just(ResolveEffect::Draw),
+            card_ref_strategy().prop_map(|card| ResolveEffect::OpponentDiscard { card }),
+            Just(ResolveEffect::OpponentGainShortCircuit),
+            Just(ResolveEffect::OpponentLoseShield),
+            (system_strategy(), system_strategy()).prop_map(|(from_system, to_system)| {
+                ResolveEffect::OpponentMoveEnergy {
+                    from_system,
+                    to_system,
+                }
+            }),
+            system_strategy().prop_map(|system| ResolveEffect::OpponentGainOverload { system }),
+            (system_strategy(), system_strategy()).prop_map(|(from_system, to_system)| {
+                ResolveEffect::MoveEnergy {
+                    from_system,
+                    to_system,
+                }
+            }),
+            (system_strategy(), system_strategy()).prop_map(|(from_system, to_system)| {
+                ResolveEffect::MoveEnergyTo {
+                    from_system,
+                    to_system,
+                }
+            }),
+            Just(ResolveEffect::BypassShield),
+        ]
+    }
+
+    fn action_strategy() -> impl Strategy<Value = Action> {
+        prop_oneof![
+            1 => (card_ref_strategy(), system_strategy(), card_refs_strategy()).prop_map(
+                |(card, system, discards)| Action::HotWireCard {
+                    card,
+                    system,
+                    discards,
+                }
+            ),
+            1 => card_ref_strategy().prop_map(|card| Action::PlayInstantCard { card }),
+            3 => (
+                system_strategy(),
+                prop::option::of(energies_strategy()),
+                prop::option::of(energies_strategy())
+            )
+                .prop_map(|(system, energy_to_use, energy_distribution)| {
+                    Action::ActivateSystem {
+                        system,
+                        energy_to_use,
+                        energy_distribution,
+                    }
+                }),
+            1 => system_strategy().prop_map(|system| Action::DiscardOverload { system }),
+            1 => Just(Action::ReduceShortCircuits),
+        ]
+    }
+
+    fn user_action_strategy() -> impl Strategy<Value = UserAction> {
+        prop_oneof![
+            3 => action_strategy().prop_map(|action| UserAction::ChooseAction { action }),
+            2 => resolve_effect_strategy()
+                .prop_map(|resolve_effect| UserAction::ResolveEffect { resolve_effect }),
+            1 => card_refs_strategy().prop_map(|discards| UserAction::Pass { discards }),
+            1 => Just(UserAction::StopResolvingEffects),
+        ]
+    }
+
+    // the player is picked relative to whoever is due to act, so that most actions aren't
+    // thrown out as being out of turn
+    fn user_actions_strategy() -> impl Strategy<Value = Vec<(bool, UserAction)>> {
+        prop::collection::vec((prop::bool::weighted(0.2), user_action_strategy()), 1..30)
+    }
+
+    // a state reached by playing legal moves from a seeded start, shrinking towards fewer moves
+    fn reachable_game_state_strategy() -> impl Strategy<Value = GameState> {
+        (any::<u64>(), prop::collection::vec(any::<Index>(), 0..40)).prop_map(|(seed, choices)| {
+            let mut game_state = GameState::start_state_with_seed(seed);
+            for choice in choices {
+                let player = game_state.next_player_to_act();
+                let legal_moves = game_state.legal_moves(player);
+                if legal_moves.is_empty() {
+                    break;
+                }
+                game_state
+                    .receive_user_action(choice.get(&legal_moves).clone())
+                    .unwrap();
+            }
+            game_state
+        })
+    }
+
+    fn check_invariants(
+        before: &GameState,
+        after: &GameState,
+        user_action_with_player: &UserActionWithPlayer,
+    ) -> Result<(), TestCaseError> {
+        prop_assert_eq!(after.get_total_cards(), get_deck().len());
+        for player_state in [&after.player1, &after.player2] {
+            prop_assert!(player_state.hull_damage >= 0);
+            prop_assert!(player_state.shields >= 0);
+            prop_assert!(player_state.short_circuits >= 0);
+            for system in System::ALL {
+                let system_state = player_state.get_system_state_immut(system);
+                prop_assert!(system_state.energy >= 0, "negative energy on {:?}", system);
+                prop_assert!(system_state.overloads >= 0);
+            }
+        }
+        prop_assert!(after.actions_left >= 0);
+        if after.players_turn != before.players_turn {
+            // only passing ends a turn, and the next one starts fresh
+            let passed = matches!(user_action_with_player.user_action, UserAction::Pass { .. });
+            prop_assert!(passed, "the turn changed without a pass");
+            prop_assert_eq!(user_action_with_player.player, before.players_turn);
+            prop_assert_eq!(after.players_turn, before.players_turn.other_player());
+            prop_assert_eq!(after.actions_left, 3);
+            prop_assert_eq!(&after.turn_state, &TurnState::ChoosingAction);
+        } else if user_action_with_player.player != before.players_turn {
+            let discarded = matches!(
+                user_action_with_player.user_action,
+                UserAction::ResolveEffect {
+                    resolve_effect: ResolveEffect::OpponentDiscard { .. }
+                }
+            );
+            prop_assert!(discarded, "the player acted out of turn");
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_arbitrary_actions_keep_invariants(
+            mut game_state in reachable_game_state_strategy(),
+            user_actions in user_actions_strategy(),
+        ) {
+            for (out_of_turn, user_action) in user_actions {
+                let player = game_state.next_player_to_act();
+                let user_action_with_player = UserActionWithPlayer {
+                    player: if out_of_turn { player.other_player() } else { player },
+                    user_action,
+                };
+                let before = game_state.clone();
+                let before_json = serde_json::to_string(&game_state).unwrap();
+                match game_state.receive_user_action(user_action_with_player.clone()) {
+                    Ok(()) => check_invariants(&before, &game_state, &user_action_with_player)?,
+                    Err(_) => {
+                        prop_assert_eq!(&game_state, &before);
+                        prop_assert_eq!(serde_json::to_string(&game_state).unwrap(), before_json);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn prop_compact_round_trip(game_state in reachable_game_state_strategy()) {
+            let compact = game_state.to_compact();
+            let decoded = GameState::from_compact(&compact);
+            prop_assert_eq!(decoded.as_ref(), Ok(&game_state));
+            prop_assert_eq!(decoded.unwrap().compact_hash(), game_state.compact_hash());
+        }
+
+        #[test]
+        fn prop_legal_moves_keep_invariants(
+            seed in any::<u64>(),
+            choices in prop::collection::vec(any::<Index>(), 0..80),
+        ) {
+            let mut game_state = GameState::start_state_with_seed(seed);
+            for choice in choices {
+                let player = game_state.next_player_to_act();
+                let legal_moves = game_state.legal_moves(player);
+                if legal_moves.is_empty() {
+                    break;
+                }
+                let user_action_with_player = choice.get(&legal_moves).clone();
+                let before = game_state.clone();
+                prop_assert_eq!(
+                    game_state.receive_user_action(user_action_with_player.clone()),
+                    Ok(())
+                );
+                check_invariants(&before, &game_state, &user_action_with_player)?;
+            }
+        }
+    }
 }